@@ -0,0 +1,375 @@
+use regex::Regex;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+pub mod macros;
+pub mod listing;
+pub mod object;
+pub mod link;
+mod peephole;
+
+use macros::Line;
+pub use object::{Object, Relocation, Target};
+pub use grumpy_isa::{Binop, Instr, Program, ToBin, Unop, Val};
+
+//Explicit operands like i32:-5 or loc:12 say what they are instead of being guessed at
+fn typed_operand(kind: &str, operand: &str) -> Result<Val, String> {
+    match kind {
+        "i32" => match operand.parse::<i32>() {
+            Ok(i) => Ok(Val::Vi32(i)),
+            Err(_) => Err(format!("PUSH: {} is not an i32", operand)),
+        },
+        "bool" => match operand {
+            "true" => Ok(Val::Vbool(true)),
+            "false" => Ok(Val::Vbool(false)),
+            _ => Err(format!("PUSH: {} is not a bool", operand)),
+        },
+        "unit" if operand == "tt" => Ok(Val::Vunit),
+        "undef" if operand == "undef" => Ok(Val::Vundef),
+        //An absolute location, never relocated by the linker
+        "loc" => Ok(Val::Vloc(is_u32(Some(operand))?)),
+        //Anything that could be defined as a label
+        "label" if check_label(&format!("{}:", operand)) => Ok(Val::Vlabel(operand.to_string())),
+        _ => Err(format!("PUSH: {}:{} is not a valid operand", kind, operand)),
+    }
+}
+
+fn push_eval(str: Option<&str>) -> Result<Instr, String> {
+    let str = str.ok_or("PUSH: missing operand")?;
+    if let Some((kind, operand)) = str.split_once(':') {
+        return Ok(Instr::Push(typed_operand(kind, operand)?));
+    }
+    match str {
+        "true" => Ok(Instr::Push(Val::Vbool(true))),
+        "false" => Ok(Instr::Push(Val::Vbool(false))),
+        "undef" => Ok(Instr::Push(Val::Vundef)),
+        "tt" => Ok(Instr::Push(Val::Vunit)),
+        other => {
+            if let Ok(i) = other.parse::<i32>() {
+                Ok(Instr::Push(Val::Vi32(i)))
+            }
+            else {
+                //Checked against the symbol table once every label has its pc
+                Ok(Instr::Push(Val::Vlabel(other.to_string())))
+            }
+            },
+    }
+}
+
+fn is_u32(str: Option<&str>) -> Result<u32, String> {
+    match str.map(|s| s.parse::<u32>()) {
+        Some(Ok(u)) => Ok(u),
+        _ => Err(format!("NOT A U32: {}", str.unwrap_or("missing operand"))),
+    }
+}
+
+fn is_uniary(str: Option<&str>) -> Result<Unop, String> {
+    str.and_then(Unop::from_mnemonic)
+        .ok_or_else(|| format!("NOT A REAL UNOP: {}", str.unwrap_or("missing operand")))
+}
+
+fn is_binary(str: Option<&str>) -> Result<Binop, String> {
+    str.and_then(Binop::from_mnemonic)
+        .ok_or_else(|| format!("NOT A REAL BINOP: {}", str.unwrap_or("missing operand")))
+}
+
+fn check_label(str: &str) -> bool {
+    let re = Regex::new(r"_*(?-i)L[[:alnum:]]+").unwrap();
+    str.contains(':') && re.is_match(str)
+}
+
+fn strip(str: &str) -> String{
+    str[0..str.len() - 1].to_string()
+}
+
+/// An assembly error and the source line it was found on, 0 when the
+/// problem is with the program as a whole.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsmError {
+    pub line_no: usize,
+    pub msg: String,
+}
+
+impl AsmError {
+    pub fn new(line_no: usize, msg: String) -> AsmError {
+        AsmError { line_no, msg }
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line_no == 0 {
+            write!(f, "{}", self.msg)
+        } else {
+            write!(f, "line {}: {}", self.line_no, self.msg)
+        }
+    }
+}
+
+impl Error for AsmError {}
+
+/// `relocatable` allows `.extern` labels, left for the linker to resolve.
+/// `optimize` runs the peephole pass before labels get their pcs.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub relocatable: bool,
+    pub optimize: bool,
+}
+
+/// Everything produced while assembling one source file. `source` is the
+/// lines that were assembled, a JSON program written out as assembly.
+pub struct Assembly {
+    pub source: Vec<String>,
+    pub object: Object,
+    pub listing: Vec<listing::Entry>,
+    pub instrs_removed: usize,
+}
+
+impl Assembly {
+    /// How much the peephole pass removed, as -O reports it.
+    pub fn peephole_summary(&self) -> String {
+        peephole::summary(self.instrs_removed, self.object.instr_count as usize)
+    }
+}
+
+//Turns one expanded line into an instruction, None for blank lines and directives
+fn parse_line(line: &Line, exports: &mut Vec<String>, imports: &mut Vec<String>) -> Result<Option<Instr>, String> {
+    let mut iter = line.text.split_whitespace();
+    let op = match iter.next() {
+        Some(op) => op,
+        None => return Ok(None),
+    };
+    let instr = match op {
+            "push" => push_eval(iter.next())?,
+            ".global" => { exports.push(iter.next().ok_or("GLOBAL: missing label")?.to_string()); return Ok(None) },
+            ".extern" => { imports.push(iter.next().ok_or("EXTERN: missing label")?.to_string()); return Ok(None) },
+            "pop" => Instr::Pop,
+            "peek" => Instr::Peek(is_u32(iter.next())?),
+            "unary" => Instr::Unary(is_uniary(iter.next())?),
+            "binary" => Instr::Binary(is_binary(iter.next())?),
+            "swap" => Instr::Swap,
+            "alloc" => Instr::Alloc,
+            "set" => Instr::Set,
+            "get" => Instr::Get,
+            "var" => Instr::Var(is_u32(iter.next())?),
+            "store" => Instr::Store(is_u32(iter.next())?),
+            "setframe" => Instr::SetFrame(is_u32(iter.next())?),
+            "call" => Instr::Call,
+            "ret" => Instr::Ret,
+            "branch" => Instr::Branch,
+            "halt" => Instr::Halt,
+            "spawn" => Instr::Spawn,
+            "join" => Instr::Join,
+            "chan" => Instr::Chan,
+            "send" => Instr::Send,
+            "recv" => Instr::Recv,
+            "close" => Instr::Close,
+            "mutex" => Instr::Mutex,
+            "acquire" => Instr::Acquire,
+            "release" => Instr::Release,
+            "cas" => Instr::Cas,
+            "print" => Instr::Print,
+            directive if directive.starts_with('.') => return Err(format!("Unknown directive {}", directive)),
+            other => {
+                if check_label(other) {
+                    Instr::Label(strip(other))
+                } else {
+                    return Err(match &line.macro_name {
+                        Some(name) => format!("NOT A PROPER INSTRUCTION: {} (expanded from macro {})", other, name),
+                        None => format!("NOT A PROPER INSTRUCTION: {}", other),
+                    });
+                }
+            },
+        };
+    Ok(Some(instr))
+}
+
+/// The assembly to assemble from `input`, which is either assembly already
+/// or a JSON program, written back out one instruction per line.
+pub fn source_text(input: &str) -> Result<String, AsmError> {
+    if !grumpy_isa::json::is_json(input.as_bytes()) {
+        return Ok(input.to_string());
+    }
+    let program = Program::from_json(input).map_err(|msg| AsmError::new(0, msg))?;
+    Ok(program.with_labels().iter().map(|instr| format!("{}\n", instr)).collect())
+}
+
+/// Assembles a whole source file: expands macros, parses every line,
+/// optionally optimizes, gives each label its pc and encodes the result.
+pub fn assemble(source: &str, options: &Options) -> Result<Assembly, AsmError> {
+    let source = source_text(source)?;
+    let source: Vec<String> = source.lines().map(|l| l.to_string()).collect();
+    let lines = macros::expand(&source)?;
+    let mut program: Vec<(Instr, usize)> = Vec::new();
+    let mut exports: Vec<String> = Vec::new();
+    let mut imports: Vec<String> = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        match parse_line(line, &mut exports, &mut imports) {
+            Ok(Some(instr)) => program.push((instr, index)),
+            Ok(None) => {},
+            Err(msg) => return Err(AsmError::new(line.line_no, msg)),
+        }
+    }
+
+    let mut instrs_removed = 0;
+    if options.optimize {
+        instrs_removed = peephole::optimize(&mut program);
+    }
+
+    //Labels only get their pcs once the instruction stream is final
+    let mut symbols = HashMap::new();
+    let mut pc = 0;
+    for (instr, index) in &program {
+        if let Instr::Label(label) = instr {
+            if symbols.contains_key(label) {
+                return Err(AsmError::new(lines[*index].line_no, format!("Multiple instances of the label {}", label)));
+            }
+            symbols.insert(label.clone(), pc);
+        } else {
+            pc += 1;
+        }
+    }
+
+    if !options.relocatable && !imports.is_empty() {
+        return Err(AsmError::new(0, format!("EXTERN: {} can only be resolved by the linker, assemble with -c", imports[0])));
+    }
+    for name in exports.iter().chain(imports.iter()) {
+        if imports.contains(name) && symbols.contains_key(name) {
+            return Err(AsmError::new(0, format!("EXTERN: {} is defined in this file", name)));
+        }
+        if exports.contains(name) && !symbols.contains_key(name) {
+            return Err(AsmError::new(0, format!("GLOBAL: {} is never defined", name)));
+        }
+    }
+
+    let mut object = Object::default();
+    let mut listing_entries = Vec::new();
+    for (instr, index) in program {
+        let line = &lines[index];
+        let instr = match instr {
+            Instr::Label(label) => {
+                listing_entries.push(listing::Entry::new(line, symbols[&label], Vec::new()));
+                continue
+            },
+            Instr::Push(Val::Vlabel(label)) => {
+                //The Vloc operand sits after the push opcode and the value tag
+                let offset = object.code.len() as u32 + 2;
+                if let Some(import) = imports.iter().position(|name| *name == label) {
+                    object.relocations.push(Relocation { offset, target: Target::Import(import as u32) });
+                    Instr::Push(Val::Vloc(0))
+                } else if let Some(target) = symbols.get(&label) {
+                    object.relocations.push(Relocation { offset, target: Target::Local });
+                    Instr::Push(Val::Vloc(*target))
+                } else {
+                    return Err(AsmError::new(line.line_no, format!("Not a valid Push instruction: {} is not a label", label)));
+                }
+            },
+            instr => instr,
+        };
+        let mut bytes = instr.to_bin();
+        listing_entries.push(listing::Entry::new(line, object.instr_count, bytes.clone()));
+        object.code.append(&mut bytes);
+        object.instr_count += 1;
+    }
+
+    object.exports = exports.into_iter().map(|name| {
+        let pc = symbols[&name];
+        (name, pc)
+    }).collect();
+    object.imports = imports;
+    object.symbols = symbols;
+    Ok(Assembly { source, object, listing: listing_entries, instrs_removed })
+}
+
+/// Assembles `source` into an executable object with default options.
+pub fn assemble_str(source: &str) -> Result<Object, AsmError> {
+    assemble(source, &Options::default()).map(|assembly| assembly.object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_operands() {
+        assert_eq!(typed_operand("i32", "-5"), Ok(Val::Vi32(-5)));
+        assert_eq!(typed_operand("loc", "12"), Ok(Val::Vloc(12)));
+        assert_eq!(typed_operand("label", "Lmain"), Ok(Val::Vlabel("Lmain".to_string())));
+    }
+
+    #[test]
+    fn typed_operands_that_do_not_fit_their_type() {
+        assert_eq!(typed_operand("bool", "3"), Err("PUSH: 3 is not a bool".to_string()));
+        assert_eq!(typed_operand("i32", "true"), Err("PUSH: true is not an i32".to_string()));
+        assert_eq!(typed_operand("unit", "3"), Err("PUSH: unit:3 is not a valid operand".to_string()));
+        assert_eq!(typed_operand("f64", "1.5"), Err("PUSH: f64:1.5 is not a valid operand".to_string()));
+        assert_eq!(typed_operand("loc", "-1"), Err("NOT A U32: -1".to_string()));
+        assert_eq!(typed_operand("loc", "L1"), Err("NOT A U32: L1".to_string()));
+        assert_eq!(typed_operand("label", ""), Err("PUSH: label: is not a valid operand".to_string()));
+        assert_eq!(typed_operand("label", "12"), Err("PUSH: label:12 is not a valid operand".to_string()));
+    }
+
+    const PROGRAM: &str = "setframe 0\npush Lmain\ncall\nhalt\nLmain:\npush 7\nret\n";
+
+    fn error(source: &str, relocatable: bool) -> AsmError {
+        let options = Options { relocatable, ..Options::default() };
+        assemble(source, &options).err().unwrap()
+    }
+
+    #[test]
+    fn assemble_str_gives_an_executable_object() {
+        let object = assemble_str(PROGRAM).unwrap();
+        assert_eq!(object.instr_count, 6);
+        assert_eq!(object.symbols.get("Lmain"), Some(&4));
+        assert_eq!(object.relocations.len(), 1);
+        let program = [Instr::SetFrame(0), Instr::Push(Val::Vloc(4)), Instr::Call, Instr::Halt, Instr::Push(Val::Vi32(7)), Instr::Ret];
+        let code: Vec<u8> = program.iter().flat_map(|instr| instr.to_bin()).collect();
+        assert_eq!(object.code, code);
+    }
+
+    #[test]
+    fn assembly_errors() {
+        assert_eq!(error("Lmain:\nLmain:\n", false), AsmError::new(2, "Multiple instances of the label Lmain".to_string()));
+        assert_eq!(error("push Lnowhere\n", false),
+                   AsmError::new(1, "Not a valid Push instruction: Lnowhere is not a label".to_string()));
+        assert_eq!(error("setframe 0\nfrob\n", false), AsmError::new(2, "NOT A PROPER INSTRUCTION: frob".to_string()));
+        assert_eq!(error(".extern Lf\n", false),
+                   AsmError::new(0, "EXTERN: Lf can only be resolved by the linker, assemble with -c".to_string()));
+        assert_eq!(error(".extern Lf\nLf:\n", true), AsmError::new(0, "EXTERN: Lf is defined in this file".to_string()));
+        assert_eq!(error(".global Lf\n", true), AsmError::new(0, "GLOBAL: Lf is never defined".to_string()));
+        assert_eq!(error(".section data\n", false), AsmError::new(1, "Unknown directive .section".to_string()));
+    }
+
+    #[test]
+    fn asm_errors_show_their_line_unless_it_is_the_whole_program() {
+        assert_eq!(AsmError::new(3, "bad".to_string()).to_string(), "line 3: bad");
+        assert_eq!(AsmError::new(0, "bad".to_string()).to_string(), "bad");
+    }
+
+    #[test]
+    fn executable_and_relocatable_objects() {
+        let options = Options { relocatable: true, ..Options::default() };
+        let object = assemble(&format!(".global Lmain\n{}", PROGRAM), &options).unwrap().object;
+        let executable = object.to_bytes();
+        assert_eq!(executable[..4], [0, 0, 0, 6]);
+        assert_eq!(executable[4..], object.code[..]);
+
+        let relocatable = object.to_relocatable();
+        assert_eq!(&relocatable[..4], b"GOBJ");
+        let read = Object::from_relocatable(&relocatable).unwrap();
+        assert_eq!((read.instr_count, &read.code), (6, &object.code));
+        assert_eq!(read.exports, [("Lmain".to_string(), 4)]);
+        assert_eq!(read.relocations.len(), 1);
+        assert_eq!(read.relocations[0].target, Target::Local);
+        assert!(Object::from_relocatable(&executable).is_err());
+    }
+
+    #[test]
+    fn typed_operand_errors_name_their_line() {
+        let err = assemble_str("setframe 0\npush bool:3\nhalt\n").err().unwrap();
+        assert_eq!(err, AsmError::new(2, "PUSH: 3 is not a bool".to_string()));
+        assert_eq!(err.to_string(), "line 2: PUSH: 3 is not a bool");
+    }
+}
@@ -0,0 +1,66 @@
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+use std::collections::HashMap;
+
+use crate::object::{Object, Target};
+
+/// Lays the objects out one after another in the order given, resolves every
+/// relocation and returns the bytes of an executable `.o`. The first object
+/// must hold the entry point at pc 0. Every undefined or duplicate symbol is
+/// reported, not just the first.
+pub fn link(objects: &[(String, Object)]) -> Result<Vec<u8>, Vec<String>> {
+    let mut errors = Vec::new();
+    let mut bases = Vec::new();
+    let mut exported: HashMap<&str, (u32, &str)> = HashMap::new();
+    let mut base = 0;
+
+    for (file, object) in objects {
+        bases.push(base);
+        for (name, pc) in &object.exports {
+            match exported.get(&**name) {
+                Some((_, other)) => errors.push(format!("duplicate symbol {}: exported by {} and {}", name, other, file)),
+                None => { exported.insert(name, (base + pc, file)); },
+            }
+        }
+        base += object.instr_count;
+    }
+
+    let mut code = Vec::new();
+    for ((file, object), base) in objects.iter().zip(bases) {
+        let mut patched = object.code.clone();
+        for reloc in &object.relocations {
+            let at = reloc.offset as usize;
+            let resolved = match reloc.target {
+                Target::Local => BigEndian::read_u32(&patched[at..at + 4]) + base,
+                Target::Import(index) => {
+                    let name = match object.imports.get(index as usize) {
+                        Some(name) => name,
+                        None => {
+                            errors.push(format!("{}: relocation refers to missing import {}", file, index));
+                            continue;
+                        },
+                    };
+                    match exported.get(&**name) {
+                        Some((pc, _)) => *pc,
+                        None => {
+                            let error = format!("undefined symbol {} referenced from {}", name, file);
+                            if !errors.contains(&error) {
+                                errors.push(error);
+                            }
+                            continue;
+                        },
+                    }
+                },
+            };
+            BigEndian::write_u32(&mut patched[at..at + 4], resolved);
+        }
+        code.append(&mut patched);
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let mut executable = Vec::new();
+    executable.write_u32::<BigEndian>(base).unwrap();
+    executable.append(&mut code);
+    Ok(executable)
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+
+use crate::macros::Line;
+
+/// One assembled line: an instruction with its encoding, or a label with no
+/// bytes whose `pc` is the address it names.
+pub struct Entry {
+    pub line_no: usize,
+    pub macro_name: Option<String>,
+    pub text: String,
+    pub pc: u32,
+    pub bytes: Vec<u8>,
+}
+
+impl Entry {
+    pub fn new(line: &Line, pc: u32, bytes: Vec<u8>) -> Entry {
+        Entry { line_no: line.line_no, macro_name: line.macro_name.clone(), text: line.text.clone(), pc, bytes }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<String>>().join(" ")
+}
+
+fn write_row(out: &mut dyn Write, line_no: Option<usize>, entry: Option<&Entry>, text: &str) -> io::Result<()> {
+    let line_col = match line_no {
+        Some(n) => n.to_string(),
+        None => String::new(),
+    };
+    let (pc_col, bytes_col) = match entry {
+        Some(e) => (e.pc.to_string(), hex(&e.bytes)),
+        None => (String::new(), String::new()),
+    };
+    writeln!(out, "{:>5}  {:>5}  {:<18} {}", line_col, pc_col, bytes_col, text)
+}
+
+/// Writes every line of `source` next to the pc and bytes it assembled to.
+/// Lines that invoked a macro are followed by the instructions they expanded
+/// into, marked with `+`. The symbol table comes last, ordered by address.
+pub fn write(out: &mut dyn Write, source: &[String], entries: &[Entry],
+             symbol_table: &HashMap<String, u32>) -> io::Result<()> {
+    let mut by_line: HashMap<usize, Vec<&Entry>> = HashMap::new();
+    for entry in entries {
+        by_line.entry(entry.line_no).or_default().push(entry);
+    }
+
+    writeln!(out, "{:>5}  {:>5}  {:<18} source", "line", "pc", "bytes")?;
+    for (index, text) in source.iter().enumerate() {
+        let line_no = index + 1;
+        match by_line.get(&line_no) {
+            Some(group) if group.len() == 1 && group[0].macro_name.is_none() => {
+                write_row(out, Some(line_no), Some(group[0]), text)?;
+            },
+            Some(group) => {
+                write_row(out, Some(line_no), None, text)?;
+                for entry in group {
+                    let name = entry.macro_name.as_deref().unwrap_or("");
+                    write_row(out, None, Some(entry), &format!("  + {:<20} ; {}", entry.text, name))?;
+                }
+            },
+            None => write_row(out, Some(line_no), None, text)?,
+        }
+    }
+
+    let mut symbols: Vec<(&String, &u32)> = symbol_table.iter().collect();
+    symbols.sort_by(|a, b| a.1.cmp(b.1).then(a.0.cmp(b.0)));
+    writeln!(out)?;
+    writeln!(out, "symbols:")?;
+    for (label, pc) in symbols {
+        writeln!(out, "{:>12}  {}", pc, label)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,269 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::AsmError;
+
+//Deepest a macro may invoke other macros before we assume it recurses forever
+const MAX_DEPTH: u32 = 64;
+
+//Pseudo-instructions every program gets for free, besides callf (see expand_callf). A macro the source defines
+//with the same name takes the place of one.
+const BUILTINS: &str = "
+.macro jmp L
+push true
+push L
+branch
+.endm
+.macro jmpif L
+push L
+branch
+.endm
+";
+
+/// A line of assembly after macro expansion. `line_no` is the line of the
+/// original source it came from, `macro_name` the macro that produced it.
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub text: String,
+    pub line_no: usize,
+    pub macro_name: Option<String>,
+}
+
+/// A `.macro name args ... .endm` definition.
+#[derive(Debug, Clone)]
+pub struct Macro {
+    pub params: Vec<String>,
+    pub body: Vec<String>,
+}
+
+type MacroTable = HashMap<String, Macro>;
+
+fn is_label(token: &str) -> bool {
+    token.len() > 1 && token.ends_with(':')
+}
+
+//Pulls every .macro/.endm block out of the source and adds it to `macros`, returning them and the lines left over.
+//A name can only be defined once in the source, but may replace one already in `macros`.
+fn collect_macros(source: &[String], mut macros: MacroTable)
+                  -> Result<(MacroTable, Vec<(usize, String)>), AsmError> {
+    let mut defined = HashSet::new();
+    let mut rest = Vec::new();
+    let mut lines = source.iter().enumerate();
+
+    while let Some((index, text)) = lines.next() {
+        let mut tokens = text.split_whitespace();
+        if tokens.next() != Some(".macro") {
+            if text.trim() == ".endm" {
+                return Err(AsmError::new(index + 1, ".endm without a matching .macro".to_string()));
+            }
+            rest.push((index + 1, text.clone()));
+            continue;
+        }
+
+        let name = match tokens.next() {
+            Some(name) => name.to_string(),
+            None => return Err(AsmError::new(index + 1, ".macro needs a name".to_string())),
+        };
+        if grumpy_isa::opcode(&name).is_some() {
+            return Err(AsmError::new(index + 1, format!("macro {} shadows an instruction", name)));
+        }
+        if !defined.insert(name.clone()) {
+            return Err(AsmError::new(index + 1, format!("macro {} is already defined", name)));
+        }
+        let params: Vec<String> = tokens.map(|t| t.to_string()).collect();
+
+        let mut body = Vec::new();
+        loop {
+            match lines.next() {
+                Some((_, line)) if line.trim() == ".endm" => break,
+                Some((inner, line)) => {
+                    if line.split_whitespace().next() == Some(".macro") {
+                        return Err(AsmError::new(inner + 1, "macro definitions cannot be nested".to_string()));
+                    }
+                    body.push(line.clone());
+                },
+                None => return Err(AsmError::new(index + 1, format!("macro {} is missing .endm", name))),
+            }
+        }
+        macros.insert(name, Macro { params, body });
+    }
+    Ok((macros, rest))
+}
+
+//callf L nargs pushes the callee, so its frame spans nargs + 1 slots, which a text macro cannot count
+fn expand_callf(tokens: &[&str], line_no: usize) -> Result<Vec<String>, AsmError> {
+    if tokens.len() != 3 {
+        return Err(AsmError::new(line_no, "callf takes a label and an argument count".to_string()));
+    }
+    let nargs = match tokens[2].parse::<u32>() {
+        Ok(nargs) => nargs,
+        Err(_) => return Err(AsmError::new(line_no, format!("callf argument count {} is not a u32", tokens[2]))),
+    };
+    Ok(vec![format!("push {}", tokens[1]), format!("setframe {}", nargs + 1), "swap".to_string(), "call".to_string()])
+}
+
+//What expanding one source file keeps track of
+struct Expander<'a> {
+    macros: &'a MacroTable,
+    //Labels the source defines outside of macros, which no renamed macro label may clash with
+    labels: HashSet<String>,
+    counter: u32,
+    out: Vec<Line>,
+}
+
+impl Expander<'_> {
+    fn expand_line(&mut self, text: &str, line_no: usize, macro_name: Option<&str>, depth: u32) -> Result<(), AsmError> {
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+        let macros = self.macros;
+        if tokens.first() == Some(&"callf") && !macros.contains_key("callf") {
+            let outer = macro_name.unwrap_or("callf");
+            for expanded in expand_callf(&tokens, line_no)? {
+                self.out.push(Line { text: expanded, line_no, macro_name: Some(outer.to_string()) });
+            }
+            return Ok(());
+        }
+        let mac = match tokens.first().and_then(|t| macros.get(*t)) {
+            Some(mac) => mac,
+            None => {
+                self.out.push(Line { text: text.to_string(), line_no, macro_name: macro_name.map(|m| m.to_string()) });
+                return Ok(());
+            },
+        };
+        let name = tokens[0];
+
+        if depth >= MAX_DEPTH {
+            return Err(AsmError::new(line_no, format!("macro {} expands too deeply, is it recursive?", name)));
+        }
+        if tokens.len() - 1 != mac.params.len() {
+            return Err(AsmError::new(line_no, format!("macro {} takes {} arguments but was given {}",
+                                                   name, mac.params.len(), tokens.len() - 1)));
+        }
+
+        //Every expansion gets its own copy of the labels defined in the body
+        self.counter += 1;
+        let mut substitute: HashMap<&str, String> = HashMap::new();
+        for body_line in &mac.body {
+            if let Some(token) = body_line.split_whitespace().next() {
+                if is_label(token) {
+                    let label = &token[..token.len() - 1];
+                    let renamed = format!("{}_m{}", label, self.counter);
+                    if self.labels.contains(&renamed) {
+                        return Err(AsmError::new(line_no, format!("label {} in macro {} is renamed to {}, which the source already defines",
+                                                               label, name, renamed)));
+                    }
+                    substitute.insert(label, renamed);
+                }
+            }
+        }
+        for (param, arg) in mac.params.iter().zip(&tokens[1..]) {
+            substitute.insert(param, arg.to_string());
+        }
+
+        let outer = macro_name.unwrap_or(name);
+        for body_line in &mac.body {
+            let replaced: Vec<String> = body_line.split_whitespace().map(|token| {
+                if is_label(token) {
+                    match substitute.get(&token[..token.len() - 1]) {
+                        Some(new) => format!("{}:", new),
+                        None => token.to_string(),
+                    }
+                } else if let Some((kind, operand)) = token.split_once(':') {
+                    //A typed operand like label:L or loc:N, where only what follows the type is a name
+                    match substitute.get(operand) {
+                        Some(new) => format!("{}:{}", kind, new),
+                        None => token.to_string(),
+                    }
+                } else {
+                    match substitute.get(token) {
+                        Some(new) => new.clone(),
+                        None => token.to_string(),
+                    }
+                }
+            }).collect();
+            if replaced.is_empty() {
+                continue;
+            }
+            self.expand_line(&replaced.join(" "), line_no, Some(outer), depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Removes the macro definitions from `source` and expands every invocation,
+/// including the built-in `jmp`, `jmpif` and `callf` unless the source
+/// defines its own, so the label pass sees only real instructions.
+pub fn expand(source: &[String]) -> Result<Vec<Line>, AsmError> {
+    let builtins: Vec<String> = BUILTINS.lines().map(|l| l.to_string()).collect();
+    let (builtins, _) = collect_macros(&builtins, HashMap::new())?;
+    let (macros, rest) = collect_macros(source, builtins)?;
+    let labels = rest.iter()
+        .filter_map(|(_, text)| text.split_whitespace().next().filter(|token| is_label(token)))
+        .map(|token| token[..token.len() - 1].to_string())
+        .collect();
+    let mut expander = Expander { macros: &macros, labels, counter: 0, out: Vec::new() };
+    for (line_no, text) in rest {
+        expander.expand_line(&text, line_no, None, 0)?;
+    }
+    Ok(expander.out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(source: &str) -> Vec<String> {
+        source.lines().map(|l| l.to_string()).collect()
+    }
+
+    fn texts(source: &str) -> Result<Vec<String>, AsmError> {
+        expand(&lines(source)).map(|out| out.into_iter().map(|line| line.text).collect())
+    }
+
+    const COUNTDOWN: &str = "
+.macro countdown n
+push n
+Lloop:
+push 1
+swap
+binary -
+peek 0
+push Lloop
+branch
+.endm
+countdown 3
+countdown 5
+";
+
+    #[test]
+    fn each_expansion_gets_its_own_labels() {
+        let out = texts(COUNTDOWN).unwrap();
+        let labels: Vec<&String> = out.iter().filter(|text| text.ends_with(':')).collect();
+        assert_eq!(labels, ["Lloop_m1:", "Lloop_m2:"]);
+        assert!(out.contains(&"push Lloop_m1".to_string()) && out.contains(&"push Lloop_m2".to_string()), "{:?}", out);
+    }
+
+    #[test]
+    fn typed_operands_are_substituted_after_their_type() {
+        let source = ".macro back L N\nLtop:\npush label:Ltop\npush label:L\npush loc:N\npush i32:N\n.endm\nback Lend 4\nback Lend 7\n";
+        assert_eq!(texts(source).unwrap(), ["Ltop_m1:", "push label:Ltop_m1", "push label:Lend", "push loc:4", "push i32:4",
+                                            "Ltop_m2:", "push label:Ltop_m2", "push label:Lend", "push loc:7", "push i32:7"]);
+    }
+
+    #[test]
+    fn renamed_labels_cannot_clash_with_the_source() {
+        let source = format!("{}Lloop_m2:\n", COUNTDOWN);
+        let err = texts(&source).unwrap_err();
+        assert_eq!(err.line_no, 13);
+        assert!(err.msg.contains("renamed to Lloop_m2"), "{}", err.msg);
+    }
+
+    #[test]
+    fn source_macros_replace_builtins_once() {
+        let source = ".macro jmp L\npush L\nbranch\n.endm\n.macro callf L\ncall L\n.endm\njmp Lend\ncallf Lf\nLend:\n";
+        assert_eq!(texts(source).unwrap(), ["push Lend", "branch", "call Lf", "Lend:"]);
+        let twice = ".macro jmp L\n.endm\n.macro jmp L\n.endm\n";
+        let err = texts(twice).unwrap_err();
+        assert_eq!((err.line_no, err.msg.as_str()), (3, "macro jmp is already defined"));
+        let err = texts(".macro push x\n.endm\n").unwrap_err();
+        assert_eq!(err.msg, "macro push shadows an instruction");
+    }
+}
@@ -0,0 +1,126 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::collections::HashMap;
+use std::io::Read;
+
+//First four bytes of every relocatable object, so a linker never mistakes an executable .o for one
+const MAGIC: &[u8; 4] = b"GOBJ";
+
+/// How a relocated `Vloc` operand gets its final value at link time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    /// A label in the same object: add the object's base pc.
+    Local,
+    /// An `.extern` label: replace with the pc it is exported at.
+    Import(u32),
+}
+
+/// `offset` is the byte offset in `code` of the u32 to patch.
+#[derive(Debug, Clone)]
+pub struct Relocation {
+    pub offset: u32,
+    pub target: Target,
+}
+
+/// Assembled code starting at pc 0, with every label's pc in `symbols`.
+/// When assembled with `.extern`s it is relocatable: `exports`, `imports`
+/// and `relocations` tell the linker how to place it among other objects.
+/// Only exports survive the trip through a relocatable object file.
+#[derive(Debug, Clone, Default)]
+pub struct Object {
+    pub instr_count: u32,
+    pub code: Vec<u8>,
+    pub symbols: HashMap<String, u32>,
+    pub exports: Vec<(String, u32)>,
+    pub imports: Vec<String>,
+    pub relocations: Vec<Relocation>,
+}
+
+fn write_name(bytes: &mut Vec<u8>, name: &str) {
+    bytes.write_u32::<BigEndian>(name.len() as u32).unwrap();
+    bytes.extend_from_slice(name.as_bytes());
+}
+
+fn read_u32(input: &mut &[u8]) -> Result<u32, String> {
+    input.read_u32::<BigEndian>().map_err(|_| "object file is truncated".to_string())
+}
+
+fn read_name(input: &mut &[u8]) -> Result<String, String> {
+    let len = read_u32(input)? as usize;
+    let mut name = vec![0; len];
+    input.read_exact(&mut name).map_err(|_| "object file is truncated".to_string())?;
+    String::from_utf8(name).map_err(|_| "symbol name is not utf-8".to_string())
+}
+
+impl Object {
+    /// The executable `.o` the vm loads: the instruction count then the code.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.write_u32::<BigEndian>(self.instr_count).unwrap();
+        bytes.extend_from_slice(&self.code);
+        bytes
+    }
+
+    pub fn to_relocatable(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.write_u32::<BigEndian>(self.instr_count).unwrap();
+        bytes.write_u32::<BigEndian>(self.code.len() as u32).unwrap();
+        bytes.extend_from_slice(&self.code);
+
+        bytes.write_u32::<BigEndian>(self.exports.len() as u32).unwrap();
+        for (name, pc) in &self.exports {
+            write_name(&mut bytes, name);
+            bytes.write_u32::<BigEndian>(*pc).unwrap();
+        }
+        bytes.write_u32::<BigEndian>(self.imports.len() as u32).unwrap();
+        for name in &self.imports {
+            write_name(&mut bytes, name);
+        }
+        bytes.write_u32::<BigEndian>(self.relocations.len() as u32).unwrap();
+        for reloc in &self.relocations {
+            bytes.write_u32::<BigEndian>(reloc.offset).unwrap();
+            match reloc.target {
+                Target::Local => bytes.push(0),
+                Target::Import(index) => {
+                    bytes.push(1);
+                    bytes.write_u32::<BigEndian>(index).unwrap();
+                },
+            }
+        }
+        bytes
+    }
+
+    pub fn from_relocatable(bytes: &[u8]) -> Result<Object, String> {
+        if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+            return Err("not a relocatable object, was it assembled with -c?".to_string());
+        }
+        let mut input = &bytes[MAGIC.len()..];
+        let mut object = Object { instr_count: read_u32(&mut input)?, ..Object::default() };
+
+        let code_len = read_u32(&mut input)? as usize;
+        object.code = vec![0; code_len];
+        input.read_exact(&mut object.code).map_err(|_| "object file is truncated".to_string())?;
+
+        for _ in 0..read_u32(&mut input)? {
+            let name = read_name(&mut input)?;
+            let pc = read_u32(&mut input)?;
+            object.symbols.insert(name.clone(), pc);
+            object.exports.push((name, pc));
+        }
+        for _ in 0..read_u32(&mut input)? {
+            object.imports.push(read_name(&mut input)?);
+        }
+        for _ in 0..read_u32(&mut input)? {
+            let offset = read_u32(&mut input)?;
+            let target = match input.read_u8() {
+                Ok(0) => Target::Local,
+                Ok(1) => Target::Import(read_u32(&mut input)?),
+                _ => return Err("bad relocation kind".to_string()),
+            };
+            if offset as usize + 4 > object.code.len() {
+                return Err(format!("relocation at byte {} is outside the code", offset));
+            }
+            object.relocations.push(Relocation { offset, target });
+        }
+        Ok(object)
+    }
+}
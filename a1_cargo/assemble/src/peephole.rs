@@ -0,0 +1,153 @@
+use crate::{Instr, Val};
+use std::collections::HashSet;
+
+//True if one of the labels directly at `from` is `target`, so a jump there goes nowhere
+fn falls_through(program: &[(Instr, usize)], from: usize, target: &str) -> bool {
+    program[from..].iter()
+        .take_while(|(instr, _)| matches!(instr, Instr::Label(_)))
+        .any(|(instr, _)| matches!(instr, Instr::Label(label) if label == target))
+}
+
+//How many instructions starting at `at` do nothing and can be dropped
+fn removable(program: &[(Instr, usize)], at: usize) -> usize {
+    let instr = |offset: usize| program.get(at + offset).map(|(instr, _)| instr);
+    match (instr(0), instr(1), instr(2)) {
+        //push tt / pop, or any other value pushed only to be popped
+        (Some(Instr::Push(_)), Some(Instr::Pop), _) => 2,
+        //var n / store n writes a slot back with its own value
+        (Some(Instr::Var(a)), Some(Instr::Store(b)), _) if a == b => 2,
+        //push true / push L / branch with L: right after it
+        (Some(Instr::Push(Val::Vbool(true))), Some(Instr::Push(Val::Vlabel(target))), Some(Instr::Branch))
+            if falls_through(program, at + 3, target) => 3,
+        _ => 0,
+    }
+}
+
+/// Removes instruction sequences that have no effect and returns how many
+/// instructions were dropped. Labels are left in place so their pcs can be
+/// recomputed afterwards, and since any label may be a branch target no
+/// pattern is matched across one. `loc:` operands are pcs already, so no
+/// pattern is matched across the instruction one points at either, and they
+/// are moved down past whatever was dropped before them.
+pub fn optimize(program: &mut Vec<(Instr, usize)>) -> usize {
+    //Where each instruction was before anything is dropped, labels have none
+    let mut pc = 0;
+    let mut pcs: Vec<Option<u32>> = program.iter().map(|(instr, _)| match instr {
+        Instr::Label(_) => None,
+        _ => {
+            pc += 1;
+            Some(pc - 1)
+        },
+    }).collect();
+    let targets: HashSet<u32> = program.iter().filter_map(|(instr, _)| match instr {
+        Instr::Push(Val::Vloc(target)) => Some(*target),
+        _ => None,
+    }).collect();
+
+    let mut removed = 0;
+    let mut at = 0;
+    while at < program.len() {
+        let count = removable(program, at);
+        if count == 0 || pcs[at + 1..at + count].iter().flatten().any(|pc| targets.contains(pc)) {
+            at += 1;
+            continue;
+        }
+        program.drain(at..at + count);
+        pcs.drain(at..at + count);
+        removed += count;
+        //Dropping these may have made the instructions on either side a pattern
+        at = at.saturating_sub(2);
+    }
+
+    //A loc: at a dropped pattern goes to what follows it, which is where the pattern would have left off
+    let kept: Vec<u32> = pcs.into_iter().flatten().collect();
+    for (instr, _) in program.iter_mut() {
+        if let Instr::Push(Val::Vloc(target)) = instr {
+            *target = kept.partition_point(|&pc| pc < *target) as u32;
+        }
+    }
+    removed
+}
+
+/// What -O prints once `removed` instructions were dropped and `kept` are left.
+pub fn summary(removed: usize, kept: usize) -> String {
+    format!("peephole: removed {} of {} instructions", removed, removed + kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Binop;
+
+    fn label(name: &str) -> Instr {
+        Instr::Label(name.to_string())
+    }
+
+    fn push_label(name: &str) -> Instr {
+        Instr::Push(Val::Vlabel(name.to_string()))
+    }
+
+    //Optimizes `instrs`, returning what is left and how many went
+    fn run(instrs: Vec<Instr>) -> (Vec<Instr>, usize) {
+        let mut program: Vec<(Instr, usize)> = instrs.into_iter().enumerate().map(|(index, instr)| (instr, index)).collect();
+        let removed = optimize(&mut program);
+        (program.into_iter().map(|(instr, _)| instr).collect(), removed)
+    }
+
+    #[test]
+    fn drops_a_push_that_is_popped() {
+        let (program, removed) = run(vec![Instr::Push(Val::Vi32(1)), Instr::Push(Val::Vunit), Instr::Pop, Instr::Ret]);
+        assert_eq!(program, vec![Instr::Push(Val::Vi32(1)), Instr::Ret]);
+        assert_eq!(removed, 2);
+    }
+
+    #[test]
+    fn drops_a_slot_stored_back_into_itself() {
+        let (program, removed) = run(vec![Instr::Var(2), Instr::Store(2), Instr::Var(2), Instr::Store(3)]);
+        assert_eq!(program, vec![Instr::Var(2), Instr::Store(3)]);
+        assert_eq!(removed, 2);
+    }
+
+    #[test]
+    fn drops_a_jump_to_the_next_label() {
+        let jump = |target| vec![Instr::Push(Val::Vbool(true)), push_label(target), Instr::Branch];
+        let program = [jump("Lnext"), vec![label("Lother"), label("Lnext"), Instr::Ret]].concat();
+        let (program, removed) = run(program);
+        assert_eq!(program, vec![label("Lother"), label("Lnext"), Instr::Ret]);
+        assert_eq!(removed, 3);
+
+        //Jumping over anything, or on a condition, has to stay
+        let over = [jump("Lnext"), vec![Instr::Ret, label("Lnext")]].concat();
+        assert_eq!(run(over.clone()), (over, 0));
+        let conditional = vec![Instr::Var(2), push_label("Lnext"), Instr::Branch, label("Lnext")];
+        assert_eq!(run(conditional.clone()), (conditional, 0));
+    }
+
+    #[test]
+    fn does_not_match_across_a_label() {
+        let program = vec![Instr::Push(Val::Vi32(1)), label("Lloop"), Instr::Pop, Instr::Var(2), label("Lback"), Instr::Store(2)];
+        assert_eq!(run(program.clone()), (program, 0));
+    }
+
+    #[test]
+    fn loc_operands_move_with_what_they_point_at() {
+        let program = vec![Instr::Push(Val::Vunit), Instr::Pop, Instr::Push(Val::Vloc(4)), Instr::Push(Val::Vloc(0)),
+                           label("Lend"), Instr::Ret];
+        let (program, removed) = run(program);
+        assert_eq!(program, vec![Instr::Push(Val::Vloc(2)), Instr::Push(Val::Vloc(0)), label("Lend"), Instr::Ret]);
+        assert_eq!(removed, 2);
+
+        //Jumping into the middle of a pattern keeps it, like a label would
+        let into = vec![Instr::Push(Val::Vloc(2)), Instr::Push(Val::Vi32(1)), Instr::Pop, Instr::Ret];
+        assert_eq!(run(into.clone()), (into, 0));
+    }
+
+    #[test]
+    fn removing_one_pattern_can_make_another() {
+        let program = vec![Instr::Push(Val::Vi32(1)), Instr::Var(2), Instr::Store(2), Instr::Pop, Instr::Binary(Binop::Add)];
+        let (program, removed) = run(program);
+        assert_eq!(program, vec![Instr::Binary(Binop::Add)]);
+        assert_eq!(removed, 4);
+        assert_eq!(summary(removed, program.len()), "peephole: removed 4 of 5 instructions");
+    }
+}
@@ -7,3 +7,46 @@ for f in tests/*.s; do
 		echo "$f: FAILED!!!"
 	fi
 done
+
+for f in tests/link/*.s; do
+	cargo run -- -c $f 2>/dev/null
+done
+cargo run -- --link tests/link/main.obj tests/link/lib.obj 2>/dev/null
+if diff tests/link/main.expected tests/link/main.o; then
+	echo "tests/link: passed"
+else
+	echo "tests/link: FAILED!!!"
+fi
+
+cargo run -- --listing -o /dev/null tests/fact-pseudo.s 2>/dev/null
+if diff tests/fact-pseudo.lst.expected tests/fact-pseudo.lst; then
+	echo "tests/fact-pseudo.lst: passed"
+else
+	echo "tests/fact-pseudo.lst: FAILED!!!"
+fi
+rm -f tests/fact-pseudo.lst
+
+#Streaming, the output names the CLI picks, and a relocatable object linked on its own
+check() {
+	if cmp -s "$2" "$3"; then
+		echo "$1: passed"
+	else
+		echo "$1: FAILED!!!"
+	fi
+}
+tmp=$(mktemp -d)
+cat tests/fact.s | cargo run -- - -o $tmp/stdin.o 2>/dev/null
+check "stdin to -o" tests/fact.expected $tmp/stdin.o
+cargo run -- - < tests/fact.s > $tmp/stdout.o 2>/dev/null
+check "stdin to stdout" tests/fact.expected $tmp/stdout.o
+cargo run -- -o - tests/fact.s > $tmp/file-stdout.o 2>/dev/null
+check "file to stdout" tests/fact.expected $tmp/file-stdout.o
+cp tests/fact.s $tmp/fact-noext
+cargo run -- $tmp/fact-noext 2>/dev/null
+check "no .s extension" tests/fact.expected $tmp/fact-noext.o
+cargo run -- -c tests/fact.s -o $tmp/fact.obj 2>/dev/null
+cargo run -- --link $tmp/fact.obj 2>/dev/null
+check "-c and --link" tests/fact.expected $tmp/fact.o
+cargo run -- -O tests/peephole.s -o $tmp/peephole.o 2>/dev/null
+check "tests/peephole.s -O" tests/peephole-O.expected $tmp/peephole.o
+rm -r $tmp
@@ -0,0 +1,24 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+push 5
+callf Lfact 1
+ret
+Lfact:
+var 0
+push 0
+binary ==
+jmpif _L1
+push 1
+var 0
+binary -
+callf Lfact 1
+var 0
+binary *
+jmp _L2
+_L1:
+push 1
+_L2:
+ret
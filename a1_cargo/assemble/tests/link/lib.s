@@ -0,0 +1,14 @@
+.global Lsquare
+.global Ldouble
+Lsquare:
+var 0
+var 0
+binary *
+ret
+Ldouble:
+var 0
+jmp Ladd
+Ladd:
+var 0
+binary +
+ret
@@ -0,0 +1,11 @@
+.extern Lsquare
+.extern Ldouble
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+push 7
+callf Lsquare 1
+callf Ldouble 1
+ret
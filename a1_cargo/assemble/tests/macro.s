@@ -0,0 +1,27 @@
+.macro jmp L
+push true
+push L
+branch
+.endm
+.macro pick c a b
+push c
+push Lyes
+branch
+push b
+jmp Ldone
+Lyes:
+push a
+Ldone:
+.endm
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+jmp Lskip
+push 99
+Lskip:
+pick true 1 2
+pick false 10 20
+binary +
+ret
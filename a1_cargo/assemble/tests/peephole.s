@@ -0,0 +1,22 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+push 5
+push 7
+store 2
+var 2
+store 2
+push tt
+pop
+push true
+push Lnext
+branch
+Lnext:
+push 1
+Lkeep:
+pop
+var 2
+binary +
+ret
@@ -0,0 +1,33 @@
+setframe 0
+push loc:4
+call
+halt
+Lmain:
+push i32:5
+push label:Lfact
+setframe 2
+swap
+call
+ret
+Lfact:
+var 0
+push i32:0
+binary ==
+push label:_L1
+branch
+push i32:1
+var 0
+binary -
+push Lfact
+setframe 2
+swap
+call
+var 0
+binary *
+push bool:true
+push _L2
+branch
+_L1:
+push 1
+_L2:
+ret
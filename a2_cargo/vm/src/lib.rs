@@ -0,0 +1,296 @@
+use grumpy_isa::{Binop, Instr, Unop, Val};
+
+
+pub const HEAP_SIZE: u32 = 1024;
+const STACK_SIZE: u32 = 1024;
+
+/// How a program is run. `fuel` caps how many instructions execute before
+/// giving up, `trace` prints each instruction to stderr as it runs.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub heap_size: u32,
+    pub fuel: Option<u64>,
+    pub trace: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config { heap_size: HEAP_SIZE, fuel: None, trace: false }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct State {
+    pub halt: bool,
+    pub pc: u32,
+    pub fp: u32,
+    pub stack: Vec<Val>,
+    pub heap: Vec<Val>,
+    pub program: Vec<Instr>
+}
+
+/// Runs `program` to its halt and returns the value left on top of the stack.
+pub fn run(program: Vec<Instr>, config: &Config) -> Result<Val, String> {
+    let program_size = program.len() as u32;
+    let mut program_state = State { halt: false, pc: 0, fp: 0, stack: Vec::new(), heap: Vec::new(), program };
+    let mut executed: u64 = 0;
+
+    while !program_state.halt {
+        if config.fuel == Some(executed) {
+            return Err(format!("out of fuel after {} instructions", executed));
+        }
+        executed += 1;
+        if config.trace {
+            if let Some(instr) = program_state.program.get(program_state.pc as usize) {
+                eprintln!("{:>5}: {}", program_state.pc, instr);
+            }
+        }
+        program_state.pc = program_state.pc + 1;
+        if program_state.pc - 1 < program_size {
+            match program_state.program.get((program_state.pc - 1) as usize).unwrap() {
+//PUSH
+                Instr::Push(val) => {program_state.stack.push(val.clone())},
+//POP
+                Instr::Pop => {program_state.stack.pop();},
+//PEEK
+                Instr::Peek(u) => {
+                    let peek_val = program_state.stack.get(*u as usize).unwrap();
+                    program_state.stack.push(peek_val.clone())},
+//UNARY
+                Instr::Unary(unop) => {
+                    match unop {
+                        Unop::Neg => {
+                            let op1 = program_state.stack.pop().unwrap();
+                            match op1 {
+                                Val::Vbool(b) => {
+                                        program_state.stack.push(Val::Vbool(!b));},
+                                _ => panic!("Cannot perform negation not on a boolean"),
+                            }},
+                        _ => panic!("Not a calid Unary operation"),
+                    }},
+//BINARY
+                Instr::Binary(binop) => {
+                    match binop {
+                        Binop::Add => {
+                            let op1 = program_state.stack.pop().unwrap();
+                            let op2 = program_state.stack.pop().unwrap();
+                            match op1 {
+                                Val::Vi32(o1) => {
+                                    match op2 {
+                                        Val::Vi32(o2) => {program_state.stack.push(Val::Vi32(o1 + o2))},
+                                        _ => panic!("Cannot perform addition on value not i32"),
+                                    }},
+                                _ => panic!("Cannot perform addition on value not i32"),
+                            }},
+                        Binop::Mul => {
+                            let op1 = program_state.stack.pop().unwrap();
+                            let op2 = program_state.stack.pop().unwrap();
+                            match op1 {
+                                Val::Vi32(o1) => {
+                                    match op2 {
+                                        Val::Vi32(o2) => {program_state.stack.push(Val::Vi32(o1 * o2))},
+                                        _ => panic!("Cannot perform multiplication on value not i32"),
+                                    }},
+                                _ => panic!("Cannot perform multiplication on value not i32"),
+                            }},
+                        Binop::Sub => {
+                            let op1 = program_state.stack.pop().unwrap();
+                            let op2 = program_state.stack.pop().unwrap();
+                            match op1 {
+                                Val::Vi32(o1) => {
+                                    match op2 {
+                                        Val::Vi32(o2) => {program_state.stack.push(Val::Vi32(o1 - o2))},
+                                        _ => panic!("Cannot perform subtraction on value not i32"),
+                                    }},
+                                _ => panic!("Cannot perform subtraction on value not i32"),
+                            }},
+                        Binop::Div => {
+                            let op1 = program_state.stack.pop().unwrap();
+                            let op2 = program_state.stack.pop().unwrap();
+                            match op1 {
+                                Val::Vi32(o1) => {
+                                    match op2 {
+                                        Val::Vi32(o2) => {
+                                            if o2 != 0 {
+                                                program_state.stack.push(Val::Vi32(o1 / o2))
+                                            }else {panic!("Cannot divide by zero!");}},
+                                        _ => panic!("Cannot perform division on value not i32"),
+                                    }},
+                                _ => panic!("Cannot perform division on value not i32"),
+                            }},
+                        Binop::Lt => {
+                            let op1 = program_state.stack.pop().unwrap();
+                            let op2 = program_state.stack.pop().unwrap();
+                            match op1 {
+                                Val::Vi32(o1) => {
+                                    match op2 {
+                                        Val::Vi32(o2) => {
+                                            if o1 < o2 {
+                                                program_state.stack.push(Val::Vbool(true));
+                                            }else {
+                                                program_state.stack.push(Val::Vbool(false));
+                                            }},
+                                        _ => panic!("Cannot perform less than on values not i32"),
+                                    }},
+                                _ => panic!("Cannot perform less than on values not i32"),
+                            }},
+                        Binop::Eq => {
+                            let op1 = program_state.stack.pop().unwrap();
+                            let op2 = program_state.stack.pop().unwrap();
+                            match op1 {
+                                Val::Vi32(o1) => {
+                                    match op2 {
+                                        Val::Vi32(o2) => {
+                                            if o1 == o2 {
+                                                program_state.stack.push(Val::Vbool(true));
+                                            }else {
+                                                program_state.stack.push(Val::Vbool(false));
+                                            }},
+                                        _ => panic!("Cannot perform equality on values not i32"),
+                                    }},
+                                _ => panic!("Cannot perform equality on values not i32"),
+                            }},
+                        _ => {panic!("Not a valid Binary operation")},
+                    }},
+//SWAP
+                Instr::Swap => {
+                    let v1 = program_state.stack.pop().unwrap();
+                    let v2 = program_state.stack.pop().unwrap();
+                    program_state.stack.push(v1);
+                    program_state.stack.push(v2);},
+//ALLOC
+                Instr::Alloc => {
+                    let unit = program_state.stack.pop().unwrap();
+                    let heap_size = program_state.stack.pop().unwrap();
+                    if let Val::Vi32(size) = heap_size {
+                        program_state.stack.push(Val::Vaddr(program_state.heap.len()));
+                        if ((program_state.heap.len() as u32) + (size as u32)) < config.heap_size {
+                            program_state.heap.push(Val::Vsize(size));
+                            for i in 1..size + 1{
+                                program_state.heap.push(unit.clone());
+                            }
+                        }else {
+                            panic!("Alloc expands Heap size beyond {}", config.heap_size);
+                        }
+                    }
+                },
+//SET
+                Instr::Set => {
+                    let heap_val = program_state.stack.pop().unwrap();
+                    let val_idx = program_state.stack.pop().unwrap();
+                    let val_base = program_state.stack.pop().unwrap();
+                    match val_idx {
+                        Val::Vi32(idx) => {
+                            match val_base {
+                                Val::Vaddr(base) => {
+                                    if (base as i32) + idx < program_state.heap.len() as i32 {
+                                        program_state.heap.remove(base + (idx as usize) + 1);
+                                        program_state.heap.insert(base + (idx as usize) + 1, heap_val.clone());
+                                    }else{
+                                        panic!("SET: base + idx larger than heap");
+                                    }},
+                                _ => panic!("Not a valid address for heap"),
+                            }},
+                        _ => panic!("Not a valid value for heap index"),
+                    }},
+//GET
+                Instr::Get => {
+                    let val_idx = program_state.stack.pop().unwrap();
+                    let val_base = program_state.stack.pop().unwrap();
+                    match val_idx {
+                        Val::Vi32(idx) => {
+                            match val_base {
+                                Val::Vaddr(base) => {
+                                    if base + (idx as usize) < program_state.heap.len() {
+                                        let heap_val = program_state.heap.get(base + (idx as usize) + 1).unwrap();
+                                        program_state.stack.push(heap_val.clone());
+                                    }else{
+                                        panic!("GET: base + idx is larger than the heap");
+                                    }},
+                                _ => panic!("Not a valid address to GET value from heap"),
+                            }},
+                        _ => panic!("Cannot GET value at non i32 index on heat"),
+                    }},
+//VAR
+                Instr::Var(u) => {
+                    if (program_state.fp + *u) < program_state.stack.len() as u32 {
+                        let nvar = program_state.stack.get((program_state.fp + *u) as usize).unwrap();
+                        program_state.stack.push(nvar.clone());
+                    }else {
+                        panic!("Var value is longer than the stack length!");
+                    }},
+//STORE
+                Instr::Store(u) => {
+                    let vnew = program_state.stack.pop().unwrap();
+                    let index = (program_state.fp + u) as usize;
+                    if (index as u32) <= (program_state.stack.len() as u32){
+                        program_state.stack.remove(index);
+                        program_state.stack.insert(index, vnew.clone());
+                    }else {
+                        panic!("STORE: index larger than size of stack!");
+                    }},
+//SETFRAME
+                Instr::SetFrame(u) => {
+                    program_state.stack.push(Val::Vloc(program_state.fp));
+                    program_state.fp = ((program_state.stack.len() - (*u as usize) - 1) as u32);
+                    },
+//CALL
+                Instr::Call => {
+                    let target_addr = program_state.stack.pop().unwrap();
+                    program_state.stack.push(Val::Vloc(program_state.pc));
+                    match target_addr {
+                        Val::Vloc(u) => program_state.pc = u,
+                        _ => panic!("Invalid location on Call operation"),
+                    }},
+//RET
+                Instr::Ret => {
+                    let vret = program_state.stack.pop().unwrap();
+                    let caller_pc = program_state.stack.pop().unwrap();
+                    let caller_fp = program_state.stack.pop().unwrap();
+                    match caller_pc{
+                        Val::Vloc(pc) => {
+                            match caller_fp {
+                                Val::Vloc(fp) => {
+                                    program_state.stack.truncate((program_state.fp) as usize);
+                                    program_state.fp = fp;
+                                    program_state.pc = pc;
+                                    program_state.stack.push(vret);},
+                                _ => panic!("Cannot return fp to a non location"),
+                        }},
+                        _ => panic!("Cannot return pc to a non location"),
+                    }},
+//BRANCH
+                Instr::Branch => {
+                    let target_location = program_state.stack.pop().unwrap();
+                    let condition = program_state.stack.pop().unwrap();
+                    match target_location {
+                        Val::Vloc(loc) => {
+                            if loc < (program_state.program.len() as u32){
+                                match condition {
+                                    Val::Vbool(b) => {
+                                    if b {
+                                        program_state.pc = loc;
+                                    }},
+                                    _ => panic!("Not a valid condition for branching"),
+                                }
+                            }else {
+                                panic!("Not a valid address to branch to");
+                            }},
+                        _ => panic!("Not a value to branch to"),
+                    }},
+//HALT
+                Instr::Halt => {
+                    program_state.halt = true;},
+//SPAWN, JOIN, CHAN, SEND, RECV, CLOSE, MUTEX, ACQUIRE, RELEASE, CAS, PRINT: only the gc VM has threads and output
+                other @ (Instr::Spawn | Instr::Join | Instr::Chan | Instr::Send | Instr::Recv | Instr::Close |
+                         Instr::Mutex | Instr::Acquire | Instr::Release | Instr::Cas | Instr::Print) => {
+                    return Err(format!("{} is not supported by this VM, run it on the gc VM", other));
+                },
+//LABEL
+                Instr::Label(label) => return Err(format!("{}: a label left in the program, it has to be assembled away", label)),
+            }
+        }else {panic!("PC is greater than program length!");}
+    }
+
+    program_state.stack.pop().ok_or_else(|| "HALT: the stack is empty".to_string())
+}
@@ -0,0 +1,567 @@
+//! The collectors alloc can run when the heap is full. All of them get the
+//! roots of every thread and channel, free everything the roots cannot reach
+//! and point the roots and the heap at where what is left has moved to.
+
+use grumpy_isa::Val;
+use std::collections::{BTreeSet, HashMap};
+use std::mem;
+
+pub trait Collector {
+    fn name(&self) -> &'static str;
+    /// Whether alloc has to collect before it can take `size` more cells.
+    fn must_collect(&mut self, heap: &[Val], size: u32, heap_size: u32) -> bool {
+        (heap.len() as u32) + size >= heap_size
+    }
+    /// Why the last must_collect that said yes did, for the collection stats.
+    fn trigger(&self) -> &'static str {
+        "heap full"
+    }
+    /// What kind of collection the last collect was, for the collection stats.
+    fn kind(&self) -> &'static str {
+        "full"
+    }
+    /// Whether `size` more cells fit once the collection is over.
+    fn fits(&self, heap: &[Val], size: u32, heap_size: u32) -> bool {
+        (heap.len() as u32) + size <= heap_size
+    }
+    /// Collects and returns how much work that took and how many cells it
+    /// moved, or what is wrong with a heap too broken to collect.
+    fn collect(&mut self, heap: &mut Vec<Val>, roots: &mut [&mut Val]) -> Result<Work, String>;
+    /// How many values are in use after collect, None while the collector
+    /// cannot tell live from garbage yet. The heap is resized by it.
+    fn live(&self, heap: &[Val]) -> Option<usize> {
+        Some(heap.len())
+    }
+    /// Puts an array of `size` copies of `unit` in the heap, returning its address.
+    fn place(&mut self, heap: &mut Vec<Val>, size: i32, unit: &Val) -> usize {
+        let addr = heap.len();
+        heap.push(Val::Vsize(size));
+        for _i in 1..size + 1 {
+            heap.push(unit.clone());
+        }
+        addr
+    }
+    /// The write barrier, called after set or cas replaced `old` in `heap[cell]`.
+    fn stored(&mut self, _heap: &[Val], _cell: usize, _old: &Val) {}
+    /// Work done after every alloc, returning how much as collect does.
+    fn step(&mut self, _heap: &mut Vec<Val>) -> u64 {
+        0
+    }
+}
+
+/// What a collection did. `pause` is the work, one unit per cell or root it
+/// went through; the program waits for all of it, so it is the pause in
+/// instructions. `copied` is how many cells ended up somewhere else.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Work {
+    pub pause: u64,
+    pub copied: u64,
+}
+
+/// Which collector alloc runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Algorithm {
+    Copying,
+    MarkCompact,
+    Generational,
+    Incremental,
+}
+
+impl Algorithm {
+    pub fn collector(&self) -> Box<dyn Collector + Send> {
+        match self {
+            Algorithm::Copying => Box::new(Copying),
+            Algorithm::MarkCompact => Box::new(MarkCompact),
+            Algorithm::Generational => Box::new(Generational::new()),
+            Algorithm::Incremental => Box::new(Incremental::new()),
+        }
+    }
+}
+
+/// Cheney style: copies what the roots reach into a new heap, then scans
+/// the new heap for the addresses in it and copies what they point to.
+pub struct Copying;
+
+//copy from [from_addr] to [from_addr + size_of_array], from [from_heap] over to [to_heap]
+fn copy(size_of_array: i32, from_addr: &usize, from_heap: &Vec<Val>, to_heap: &mut Vec<Val>) {
+    
+    //got from index -> index + size_of_array
+    for index in 0..size_of_array + 1 {
+        let from_heap_val = from_heap.get(*from_addr + index as usize).unwrap().clone();
+        to_heap.push(from_heap_val);
+    }
+}
+
+//The size of the array that starts at `addr`, which `what` points at, or why there is none there to copy
+fn array_at(heap: &[Val], addr: usize, what: &str) -> Result<i32, String> {
+    match heap.get(addr) {
+        Some(Val::Vsize(size)) if addr + ((*size).max(0) as usize) < heap.len() => Ok(*size),
+        Some(Val::Vsize(size)) => Err(format!("{} points at {}, an array of {} values past the end of the heap at {}",
+                                              what, addr, size, heap.len())),
+        Some(_) => Err(format!("{} points at {}, which is not where an array starts", what, addr)),
+        None => Err(format!("{} points at {}, past the end of the heap at {}", what, addr, heap.len())),
+    }
+}
+
+impl Collector for Copying {
+    fn name(&self) -> &'static str {
+        "copying"
+    }
+
+    fn collect(&mut self, heap: &mut Vec<Val>, roots: &mut [&mut Val]) -> Result<Work, String> {
+        let work = (roots.len() + heap.len()) as u64;
+        let mut to_space: Vec<Val> = Vec::new();
+        let mut address_track: HashMap<usize, usize> = HashMap::new();
+        let mut scan: u32 = 0;
+
+        //FOR each root address
+        for root in roots.iter_mut() {
+            let root_val = root.clone();
+            //If the root is an address, then proceed..
+            if let Val::Vaddr(from_addr) = root_val {
+                //if the pointer to the from_heap has not been copied over yet, then update it
+                if !address_track.contains_key(&from_addr){
+                    address_track.insert(from_addr, to_space.len());
+                    //Copying a cell or two of the middle of an array would leave a heap that cannot be walked
+                    let array_size = array_at(heap, from_addr, "a root")?;
+                    copy(array_size, &from_addr, &heap, &mut to_space);
+                }else {
+                }
+                //either way the address need to be updated to the new address on the to_heap
+                **root = Val::Vaddr(*address_track.get(&from_addr).unwrap());
+            }
+            //Otherwise, do nothing at all, ONLY concerned with addresses in the roots.
+        }
+
+        //Time to scan through the to_heap and search for addresses, up to its end as that keeps moving
+        while (scan as usize) < to_space.len() {
+            let to_heap_val = to_space.get(scan as usize).unwrap().clone();
+            if let Val::Vaddr(to_addr) = to_heap_val {
+                //check if the address it points to has already been copied, and if not copy over and
+                //update address_tracker
+                if !address_track.contains_key(&to_addr) {
+                    //It has not yet been copied over, so first copy then update teh address value
+                    address_track.insert(to_addr, to_space.len());
+                    let from_array_size = array_at(heap, to_addr, &format!("heap[{}]", scan))?;
+                    copy(from_array_size, &to_addr, &heap, &mut to_space);
+                }
+            
+                to_space.remove(scan as usize);
+                to_space.insert(scan as usize, Val::Vaddr(*address_track.get(&to_addr).unwrap()));
+
+            }
+        
+            scan = scan + 1;
+        }
+
+        //Updates the stack to point to the new location of chunk that is now in the to_space
+        heap.clear();
+        heap.append(&mut to_space);
+        Ok(Work { pause: work, copied: heap.len() as u64 })
+    }
+}
+
+/// Sliding mark-compact: marks what the roots reach, works out where each
+/// live array ends up once the dead ones before it are squeezed out, points
+/// every address there and then slides the arrays down. It works in place
+/// and keeps the arrays in the order they were allocated; besides the heap
+/// it only needs a mark per cell and the old and new address of each live
+/// array.
+pub struct MarkCompact;
+
+//How many cells the array at addr takes, its size and then its elements
+fn span(heap: &[Val], addr: usize) -> usize {
+    match heap.get(addr) {
+        Some(Val::Vsize(size)) => *size as usize + 1,
+        _ => 1,
+    }
+}
+
+//Slides the live arrays at or above `from` down over the dead ones and
+//leaves everything below `from` where it is. What is below counts as live
+//without being traced, and `old_cells` are the cells down there that may
+//point above `from`, so they are roots too. Returns how many of the cells
+//that survived moved.
+fn compact(heap: &mut Vec<Val>, roots: &mut [&mut Val], from: usize, old_cells: &[usize]) -> usize {
+    //Mark everything above from reachable from the roots
+    let mut marked = vec![false; heap.len()];
+    let mut work: Vec<usize> = roots.iter().map(|root| &**root)
+        .chain(old_cells.iter().map(|&cell| &heap[cell]))
+        .filter_map(|val| match *val {
+            Val::Vaddr(addr) if addr >= from => Some(addr),
+            _ => None,
+        }).collect();
+    while let Some(addr) = work.pop() {
+        if addr >= heap.len() || marked[addr] {
+            continue;
+        }
+        marked[addr] = true;
+        for cell in &heap[addr + 1..(addr + span(heap, addr)).min(heap.len())] {
+            match cell {
+                Val::Vaddr(next) if *next >= from => work.push(*next),
+                _ => (),
+            }
+        }
+    }
+
+    //Walk the arrays in order, each live one moves down to the first free cell
+    let mut moves: Vec<(usize, usize)> = Vec::new();
+    let mut free = from;
+    let mut addr = from;
+    while addr < heap.len() {
+        let cells = span(heap, addr);
+        if marked[addr] {
+            moves.push((addr, free));
+            free += cells;
+        }
+        addr += cells;
+    }
+    let forward = |addr: usize| moves.binary_search_by_key(&addr, |&(old, _)| old).ok().map(|i| Val::Vaddr(moves[i].1));
+
+    //Point the roots and the live arrays at where things are going
+    for root in roots.iter_mut() {
+        if let Val::Vaddr(addr) = **root {
+            if let Some(moved) = forward(addr) {
+                **root = moved;
+            }
+        }
+    }
+    let live_cells = moves.iter().flat_map(|&(old, _)| old + 1..old + span(heap, old));
+    let cells: Vec<usize> = old_cells.iter().cloned().chain(live_cells).collect();
+    for cell in cells {
+        if let Val::Vaddr(addr) = heap[cell] {
+            if let Some(moved) = forward(addr) {
+                heap[cell] = moved;
+            }
+        }
+    }
+
+    //Slide, lowest first so nothing live is written over
+    let mut moved = 0;
+    for &(old, new) in moves.iter().filter(|&&(old, new)| old != new) {
+        let cells = span(heap, old);
+        for cell in 0..cells {
+            heap.swap(new + cell, old + cell);
+        }
+        moved += cells;
+    }
+    heap.truncate(free);
+    moved
+}
+
+impl Collector for MarkCompact {
+    fn name(&self) -> &'static str {
+        "mark-compact"
+    }
+
+    fn collect(&mut self, heap: &mut Vec<Val>, roots: &mut [&mut Val]) -> Result<Work, String> {
+        let work = (roots.len() + heap.len()) as u64;
+        let moved = compact(heap, roots, 0, &[]);
+        Ok(Work { pause: work, copied: moved as u64 })
+    }
+}
+
+/// Two generations in the one heap: the old one at the bottom and the
+/// nursery, everything allocated since the last collection, above it. When
+/// the nursery has taken a quarter of the heap a minor collection compacts
+/// just the nursery and promotes whatever survived to the old generation.
+/// It traces from the roots and from the remembered set, the old cells the
+/// write barrier saw being pointed at the nursery, and never looks at the
+/// rest of the old generation. Only when the old generation leaves no room
+/// for another nursery does a major collection compact the whole heap.
+pub struct Generational {
+    old: usize,
+    remembered: BTreeSet<usize>,
+    wanted: u32,
+    heap_size: u32,
+    trigger: &'static str,
+    kind: &'static str,
+}
+
+impl Generational {
+    pub fn new() -> Generational {
+        Generational { old: 0, remembered: BTreeSet::new(), wanted: 0, heap_size: 0, trigger: "nursery full", kind: "minor" }
+    }
+
+    fn nursery(&self) -> usize {
+        (self.heap_size / 4).max(1) as usize
+    }
+}
+
+impl Default for Generational {
+    fn default() -> Generational {
+        Generational::new()
+    }
+}
+
+impl Collector for Generational {
+    fn name(&self) -> &'static str {
+        "generational"
+    }
+
+    fn must_collect(&mut self, heap: &[Val], size: u32, heap_size: u32) -> bool {
+        self.old = self.old.min(heap.len());
+        self.wanted = size;
+        self.heap_size = heap_size;
+        let full = (heap.len() as u32) + size >= heap_size;
+        self.trigger = if full { "heap full" } else { "nursery full" };
+        heap.len() - self.old + size as usize >= self.nursery() || full
+    }
+
+    fn trigger(&self) -> &'static str {
+        self.trigger
+    }
+
+    fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    fn collect(&mut self, heap: &mut Vec<Val>, roots: &mut [&mut Val]) -> Result<Work, String> {
+        let young = heap.len() - self.old;
+        let remembered: Vec<usize> = mem::take(&mut self.remembered).into_iter().filter(|&cell| cell < self.old).collect();
+        let mut work = (roots.len() + remembered.len() + young) as u64;
+        let mut moved = compact(heap, roots, self.old, &remembered);
+        self.old = heap.len();
+        self.kind = "minor";
+
+        if heap.len() + self.nursery().max(self.wanted as usize) >= self.heap_size as usize {
+            let before = heap.len();
+            work += (roots.len() + before) as u64;
+            let major = compact(heap, roots, 0, &[]);
+            self.old = heap.len();
+            self.kind = "major";
+            moved += major;
+        }
+        Ok(Work { pause: work, copied: moved as u64 })
+    }
+
+    fn stored(&mut self, heap: &[Val], cell: usize, _old: &Val) {
+        match heap[cell] {
+            Val::Vaddr(addr) if cell < self.old && addr >= self.old => {
+                self.remembered.insert(cell);
+            },
+            _ => (),
+        }
+    }
+}
+
+/// Units of work the incremental collector does after every alloc.
+pub const INCREMENT: u64 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    Idle,
+    Mark,
+    //Sweeping, up to the array at this address
+    Sweep(usize),
+}
+
+/// Incremental mark-sweep that never moves an array. A cycle starts once
+/// half the heap is in use by shading what the roots point at grey. After
+/// that every alloc does INCREMENT units of work: blackening grey arrays by
+/// shading what they point at, then, once nothing is grey, sweeping the
+/// white ones into free blocks that alloc fills again. Arrays allocated
+/// during a cycle are black. The barrier on set and cas shades what they
+/// overwrite, so everything reachable when the cycle started gets marked
+/// even if the program moves it around meanwhile, and a black array never
+/// ends up the only way to reach a white one. Only when the program
+/// allocates faster than the collector frees does alloc wait for the rest
+/// of the cycle.
+pub struct Incremental {
+    phase: Phase,
+    marked: Vec<bool>,
+    grey: Vec<usize>,
+    //Address and cells of each free block, kept as an array of units so the heap can still be walked
+    free: Vec<(usize, usize)>,
+    wanted: u32,
+    heap_size: u32,
+    trigger: &'static str,
+}
+
+impl Incremental {
+    pub fn new() -> Incremental {
+        Incremental { phase: Phase::Idle, marked: Vec::new(), grey: Vec::new(), free: Vec::new(), wanted: 0, heap_size: 0,
+                      trigger: "half full" }
+    }
+
+    fn shade(&mut self, val: &Val) {
+        if let Val::Vaddr(addr) = *val {
+            if addr < self.marked.len() && !self.marked[addr] {
+                self.marked[addr] = true;
+                self.grey.push(addr);
+            }
+        }
+    }
+
+    fn start(&mut self, heap: &[Val], roots: &[&mut Val]) -> u64 {
+        self.marked = vec![false; heap.len()];
+        self.grey.clear();
+        for root in roots {
+            self.shade(root);
+        }
+        self.phase = Phase::Mark;
+        roots.len() as u64
+    }
+
+    //Does about budget units of work, returning how many it did
+    fn work(&mut self, heap: &mut Vec<Val>, budget: u64) -> u64 {
+        let mut work = 0;
+        while work < budget {
+            match self.phase {
+                Phase::Idle => break,
+                Phase::Mark => match self.grey.pop() {
+                    Some(addr) => {
+                        let end = (addr + span(heap, addr)).min(heap.len());
+                        for cell in &heap[addr + 1..end] {
+                            self.shade(cell);
+                        }
+                        work += (end - addr) as u64;
+                    },
+                    None => {
+                        self.free.clear();
+                        self.phase = Phase::Sweep(0);
+                    },
+                },
+                Phase::Sweep(addr) if addr >= heap.len() => {
+                    self.phase = Phase::Idle;
+                },
+                Phase::Sweep(addr) if self.marked[addr] => {
+                    self.phase = Phase::Sweep(addr + span(heap, addr));
+                    work += 1;
+                },
+                Phase::Sweep(start) => {
+                    //Join the white arrays up to the budget, and the free block right before them, into one free
+                    //block, or give them back if they end the heap
+                    let mut end = start;
+                    while end < heap.len() && !self.marked[end] && work + ((end - start) as u64) < budget {
+                        end += span(heap, end);
+                    }
+                    let from = match self.free.last() {
+                        Some(&(addr, cells)) if addr + cells == start => {
+                            self.free.pop();
+                            addr
+                        },
+                        _ => start,
+                    };
+                    if end == heap.len() {
+                        heap.truncate(from);
+                        self.marked.truncate(from);
+                    }else {
+                        for cell in &mut heap[start..end] {
+                            *cell = Val::Vunit;
+                        }
+                        heap[from] = Val::Vsize((end - from - 1) as i32);
+                        self.free.push((from, end - from));
+                    }
+                    self.phase = Phase::Sweep(end);
+                    work += (end - start) as u64;
+                },
+            }
+        }
+        work
+    }
+}
+
+impl Default for Incremental {
+    fn default() -> Incremental {
+        Incremental::new()
+    }
+}
+
+impl Collector for Incremental {
+    fn name(&self) -> &'static str {
+        "incremental"
+    }
+
+    fn must_collect(&mut self, heap: &[Val], size: u32, heap_size: u32) -> bool {
+        self.wanted = size;
+        self.heap_size = heap_size;
+        let free: usize = self.free.iter().map(|&(_, cells)| cells).sum();
+        let idle = self.phase == Phase::Idle && heap.len() - free + size as usize >= heap_size as usize / 2;
+        let full = !self.fits(heap, size, heap_size);
+        self.trigger = if full { "heap full" } else { "half full" };
+        idle || full
+    }
+
+    fn trigger(&self) -> &'static str {
+        self.trigger
+    }
+
+    //Whether the collection only got a cycle going or the program had to wait until one was over
+    fn kind(&self) -> &'static str {
+        if self.phase == Phase::Idle { "cycle finished" } else { "cycle started" }
+    }
+
+    fn fits(&self, heap: &[Val], size: u32, heap_size: u32) -> bool {
+        (heap.len() as u32) + size <= heap_size || self.free.iter().any(|&(_, cells)| cells > size as usize)
+    }
+
+    fn collect(&mut self, heap: &mut Vec<Val>, roots: &mut [&mut Val]) -> Result<Work, String> {
+        let mut work = 0;
+        if self.phase == Phase::Idle {
+            work += self.start(heap, roots);
+        }
+        //The program outran the collector, so it waits for the rest of the cycle, and for a whole
+        //new one if what was garbage at the start of this one is not enough
+        if !self.fits(heap, self.wanted, self.heap_size) {
+            work += self.work(heap, u64::MAX);
+            if !self.fits(heap, self.wanted, self.heap_size) {
+                work += self.start(heap, roots);
+                work += self.work(heap, u64::MAX);
+            }
+        }
+        //Nothing moves
+        Ok(Work { pause: work, copied: 0 })
+    }
+
+    fn live(&self, heap: &[Val]) -> Option<usize> {
+        match self.phase {
+            Phase::Idle => Some(heap.len() - self.free.iter().map(|&(_, cells)| cells).sum::<usize>()),
+            _ => None,
+        }
+    }
+
+    fn place(&mut self, heap: &mut Vec<Val>, size: i32, unit: &Val) -> usize {
+        let cells = size as usize + 1;
+        let addr = match self.free.iter().position(|&(_, free)| free >= cells) {
+            Some(block) => {
+                let (addr, free) = self.free[block];
+                if free == cells {
+                    self.free.remove(block);
+                }else {
+                    heap[addr + cells] = Val::Vsize((free - cells - 1) as i32);
+                    self.free[block] = (addr + cells, free - cells);
+                }
+                heap[addr] = Val::Vsize(size);
+                for cell in &mut heap[addr + 1..addr + cells] {
+                    *cell = unit.clone();
+                }
+                addr
+            },
+            None => {
+                let addr = heap.len();
+                heap.push(Val::Vsize(size));
+                for _i in 1..size + 1 {
+                    heap.push(unit.clone());
+                }
+                addr
+            },
+        };
+        //Allocated during a cycle means black
+        if self.marked.len() < heap.len() {
+            self.marked.resize(heap.len(), false);
+        }
+        self.marked[addr] = self.phase != Phase::Idle;
+        addr
+    }
+
+    fn stored(&mut self, _heap: &[Val], _cell: usize, old: &Val) {
+        if self.phase == Phase::Mark {
+            self.shade(old);
+        }
+    }
+
+    fn step(&mut self, heap: &mut Vec<Val>) -> u64 {
+        self.work(heap, INCREMENT)
+    }
+}
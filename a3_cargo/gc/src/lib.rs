@@ -0,0 +1,836 @@
+use grumpy_isa::{Binop, Instr, Unop, Val};
+use std::collections::VecDeque;
+use std::char;
+use std::fmt;
+use std::mem;
+use std::path::PathBuf;
+use std::time::Instant;
+
+pub mod collector;
+pub mod parallel;
+pub mod scheduler;
+pub mod stats;
+pub mod verify;
+pub use collector::{Algorithm, Collector, Work, INCREMENT};
+pub use scheduler::{Policy, Scheduler, QUANTUM};
+pub use stats::{Event, Stats, CELL_BYTES};
+pub use verify::verify;
+
+pub const HEAP_SIZE: u32 = 1024;
+/// The most values the heap grows to unless Growth::max says otherwise.
+pub const HEAP_MAX: u32 = 1 << 20;
+/// The status the binaries exit with when the threads deadlock.
+pub const DEADLOCK_EXIT: i32 = 3;
+
+/// How a program is run. `fuel` caps how many instructions execute, counted
+/// across every thread, and `trace` prints each one to stderr as it runs.
+/// `quantum` is the most instructions a thread runs before `policy` picks
+/// the next one, when the threads take turns, and has to be at least 1. `collector` is the collector
+/// alloc runs when the heap is full. The heap starts out holding `heap_size`
+/// values and `growth` says how it changes from there. `verify` checks the
+/// heap after every collection, and `stress` collects before every alloc,
+/// whether the heap is full or not, and verifies as well. `stats` is the file every collection is
+/// written to as a line of JSON (see stats.rs).
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub heap_size: u32,
+    pub growth: Growth,
+    pub stress: bool,
+    pub verify: bool,
+    pub stats: Option<PathBuf>,
+    pub fuel: Option<u64>,
+    pub trace: bool,
+    pub quantum: u32,
+    pub policy: Policy,
+    pub backend: Backend,
+    pub collector: Algorithm,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config { heap_size: HEAP_SIZE, growth: Growth::default(), stress: false, verify: false, stats: None, fuel: None, trace: false,
+                 quantum: QUANTUM, policy: Policy::RoundRobin, backend: Backend::Green, collector: Algorithm::Copying }
+    }
+}
+
+/// How the heap is resized after a collection. It grows `factor` times over
+/// until what is live, with the array alloc wants, fills at most
+/// `live_ratio` of it, but never past `max`; an alloc that still does not
+/// fit traps. A heap so empty that it would be less than half that full
+/// after shrinking shrinks `factor` times, but never below where it started.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Growth {
+    pub factor: f64,
+    pub max: u32,
+    pub live_ratio: f64,
+}
+
+impl Default for Growth {
+    fn default() -> Growth {
+        Growth { factor: 2.0, max: HEAP_MAX, live_ratio: 0.5 }
+    }
+}
+
+impl Growth {
+    fn grow(&self, heap_size: u32, max: u32) -> u32 {
+        ((heap_size as f64 * self.factor) as u32).max(heap_size + 1).min(max)
+    }
+
+    fn shrink(&self, heap_size: u32, initial: u32) -> u32 {
+        ((heap_size as f64 / self.factor) as u32).max(initial)
+    }
+}
+
+/// How threads run: taking turns on the one OS thread run() is called on,
+/// or each on an OS thread of its own (see parallel.rs).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    Green,
+    Os,
+}
+
+/// Why run() stopped without a result. A deadlock is kept apart from the
+/// rest so that it can be told apart by exit status; it holds a line for
+/// each thread saying where it is and what it waits for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunError {
+    Failed(String),
+    Deadlock(Vec<String>),
+    //An array of size values does not fit even with the heap at its largest
+    OutOfMemory { size: u32, max: u32 },
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunError::Failed(msg) => write!(f, "{}", msg),
+            RunError::Deadlock(threads) => write!(f, "deadlock, main can never go ahead\n  {}", threads.join("\n  ")),
+            RunError::OutOfMemory { size, max } =>
+                write!(f, "out of memory, an array of {} values does not fit in the largest heap of {} values", size, max),
+        }
+    }
+}
+
+/// What a parked thread is waiting for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wait {
+    Join(usize),
+    Recv(usize),
+    Acquire(usize),
+}
+
+/// One thread. `parked` is set when the last instruction could not go
+/// ahead, like a `join` on a thread that is still running; the pc is left on
+/// that instruction and the scheduler skips the thread until what it waits
+/// for has happened, then runs the instruction again.
+#[derive(Debug, Clone, Default)]
+pub struct State {
+    pub halt: bool,
+    pub parked: Option<Wait>,
+    pub pc: u32,
+    pub fp: u32,
+    pub stack: Vec<Val>,
+    pub program: Vec<Instr>
+}
+
+/// A channel between threads. Sends never block, values queue up until
+/// they are received.
+#[derive(Debug, Clone, Default)]
+pub struct Channel {
+    pub queue: VecDeque<Val>,
+    pub closed: bool,
+}
+
+/// Everything the threads share besides the heap. A mutex is the thread
+/// holding it, None while it is free.
+#[derive(Debug, Default)]
+pub struct Shared {
+    pub channels: Vec<Channel>,
+    pub mutexes: Vec<Option<usize>>,
+    //How many values the heap holds before alloc collects, it grows and shrinks as Config::growth says
+    pub heap_size: u32,
+    pub stats: Stats,
+    //Where a spawned thread returns to so that it halts, the program's first halt
+    pub halt_location: Option<u32>,
+}
+
+impl Shared {
+    //A parked thread can run again once what it waits for has happened
+    fn ready(&self, wait: Wait, threads: &[State]) -> bool {
+        match wait {
+            Wait::Join(thread) => threads[thread].halt,
+            Wait::Recv(channel) => !self.channels[channel].queue.is_empty() || self.channels[channel].closed,
+            Wait::Acquire(mutex) => self.mutexes[mutex].is_none(),
+        }
+    }
+
+    //Only one thread can wake a thread waiting to join or to acquire, while anyone could send to a channel
+    fn waits_on(&self, wait: Wait) -> Option<usize> {
+        match wait {
+            Wait::Join(thread) => Some(thread),
+            Wait::Acquire(mutex) => self.mutexes[mutex],
+            Wait::Recv(_) => None,
+        }
+    }
+
+    //A parked thread is stuck for good when following what it waits on leads back around to a thread already
+    //seen, or to a thread that halted holding the mutex it waits for, whatever the other threads do
+    fn stuck(&self, thread: usize, threads: &[State]) -> bool {
+        let mut seen = vec![thread];
+        let mut current = thread;
+        while let Some(wait) = threads[current].parked {
+            if self.ready(wait, threads) {
+                return false;
+            }
+            match self.waits_on(wait) {
+                Some(next) if threads[next].halt || seen.contains(&next) => return true,
+                Some(next) => {
+                    seen.push(next);
+                    current = next;
+                },
+                None => return false,
+            }
+        }
+        false
+    }
+
+    fn describe(&self, thread: usize, threads: &[State]) -> String {
+        let state = &threads[thread];
+        if state.halt {
+            return format!("thread {} halted", thread);
+        }
+        let at = format!("thread {} at pc {}", thread, state.pc);
+        let instr = state.program.get(state.pc as usize).map(|instr| instr.to_string()).unwrap_or_default();
+        match state.parked {
+            None => format!("{} can run", at),
+            Some(Wait::Join(target)) => format!("{} blocked on {}, waiting for thread {} to halt", at, instr, target),
+            Some(Wait::Recv(channel)) => format!("{} blocked on {}, waiting for a value on chan:{}", at, instr, channel),
+            Some(Wait::Acquire(mutex)) => match self.mutexes[mutex] {
+                Some(owner) => format!("{} blocked on {}, waiting for mutex:{} held by thread {}", at, instr, mutex, owner),
+                None => format!("{} blocked on {}, waiting for mutex:{}", at, instr, mutex),
+            },
+        }
+    }
+}
+
+//Why alloc collects: which thread wants how many values, and whether the collector said it has to or it is only
+//Config::stress
+#[derive(Debug, Clone, Copy)]
+struct Request {
+    thread: usize,
+    size: u32,
+    due: bool,
+}
+
+//Every thread allocates from and points into the one heap, so every thread's stack is a root, and so is every value
+//waiting in a channel, and `unit` when alloc has already taken it off the stack. Afterwards the heap is resized for
+//`size` more values, the collection goes in the stats and the length of the pause in instructions is returned.
+fn collect_garbage(collector: &mut dyn Collector, heap: &mut Vec<Val>, threads: &mut [State], shared: &mut Shared,
+                   unit: Option<&mut Val>, request: Request, config: &Config) -> Result<u64, RunError> {
+    let start = Instant::now();
+    let Request { thread, size, due } = request;
+    let mut event = Event {
+        collection: shared.stats.collections + 1,
+        collector: collector.name(),
+        kind: "",
+        trigger: if due { collector.trigger() } else { "stress" },
+        thread,
+        wanted: size,
+        heap_size: shared.heap_size,
+        objects_before: stats::objects(heap),
+        slots_before: heap.len(),
+        objects_after: 0,
+        slots_after: 0,
+        live_after: None,
+        copied: 0,
+        roots: threads.iter().map(|state| state.stack.len()).collect(),
+        channel_roots: shared.channels.iter().map(|channel| channel.queue.len()).sum(),
+        pause: 0,
+        resized_to: 0,
+    };
+    if unit.is_some() {
+        event.roots[thread] += 1;
+    }
+
+    let stacks = threads.iter_mut().flat_map(|thread| thread.stack.iter_mut());
+    let queues = shared.channels.iter_mut().flat_map(|channel| channel.queue.iter_mut());
+    let mut roots: Vec<&mut Val> = stacks.chain(queues).chain(unit).collect();
+    let Work { pause, copied } = collector.collect(heap, &mut roots)
+        .map_err(|err| RunError::Failed(format!("GC: the {} collector cannot collect a broken heap: {}", collector.name(), err)))?;
+    if config.verify || config.stress {
+        let roots: Vec<&Val> = roots.iter().map(|root| &**root).collect();
+        verify(heap, &roots)
+            .map_err(|err| RunError::Failed(format!("GC: the heap is broken after the {} collector ran: {}", collector.name(), err)))?;
+    }
+
+    let heap_size = &mut shared.heap_size;
+    let growth = &config.growth;
+    let max = growth.max.max(config.heap_size);
+    let wanted = collector.live(heap).map(|live| (live as f64) + (size as f64));
+    let mut resized = *heap_size;
+    while resized < max && (wanted > Some(resized as f64 * growth.live_ratio) || !collector.fits(heap, size, resized)) {
+        resized = growth.grow(resized, max);
+    }
+    loop {
+        let smaller = growth.shrink(resized, config.heap_size);
+        match wanted {
+            Some(wanted) if smaller < resized && wanted <= smaller as f64 * growth.live_ratio / 2.0
+                            && collector.fits(heap, size, smaller) => resized = smaller,
+            _ => break,
+        }
+    }
+    *heap_size = resized;
+
+    event.kind = collector.kind();
+    event.objects_after = stats::objects(heap);
+    event.slots_after = heap.len();
+    event.live_after = collector.live(heap);
+    event.copied = copied;
+    event.pause = pause;
+    event.resized_to = *heap_size;
+    if config.trace {
+        eprintln!("gc: {} {} collection {} took {:?}, {} of {} values left, heap resized from {} to {}", event.collector,
+                  event.kind, event.collection, start.elapsed(), event.slots_after, event.slots_before, event.heap_size,
+                  event.resized_to);
+    }
+    shared.stats.record(&event)
+        .map_err(|err| RunError::Failed(format!("GC: cannot write the stats: {}", err)))?;
+
+    if !collector.fits(heap, size, *heap_size) {
+        return Err(RunError::OutOfMemory { size, max });
+    }
+    Ok(pause)
+}
+
+fn open_stats(config: &Config) -> Result<Stats, RunError> {
+    let path = config.stats.as_deref();
+    Stats::new(path).map_err(|err| {
+        let path = path.map(|path| path.display().to_string()).unwrap_or_default();
+        RunError::Failed(format!("GC: cannot write the stats to {}: {}", path, err))
+    })
+}
+
+//However the run ended, writes out the rest of the stats and hands them back with the outcome
+fn report(mut stats: Stats, outcome: Result<Val, RunError>) -> (Result<Val, RunError>, Stats) {
+    let written = stats.finish();
+    let outcome = outcome.and_then(|value| {
+        written.map_err(|err| RunError::Failed(format!("GC: cannot write the stats: {}", err)))?;
+        Ok(value)
+    });
+    (outcome, stats)
+}
+
+
+fn channel<'a>(handle: Val, instr: &str, shared: &'a mut Shared) -> &'a mut Channel {
+    match handle {
+        Val::Vchan(c) if (c as usize) < shared.channels.len() => &mut shared.channels[c as usize],
+        _ => panic!("{}: Value in stack is not a channel", instr),
+    }
+}
+
+//The thread SPAWN starts, running the closure at closure_address on a copy of the program
+fn spawned(closure_address: Val, heap: &[Val], program_state: &State, halt_location: Option<u32>) -> State {
+    let mut new_thread_stack: Vec<Val> = Vec::new();
+    let mut new_thread_program: Vec<Instr> = Vec::new();
+    let funptr_location: Val;
+    let funptr: u32;
+
+    if let Val::Vaddr(closure) = closure_address {
+        funptr_location = heap.get(closure + (1 as usize)).unwrap().clone();
+        if let Val::Vloc(location) = funptr_location {
+            funptr = location;
+        }else {
+            panic!("SPAWN: closure address in stack does not point to a Vloc");
+        }
+    }else {
+        panic!("SPAWN: Value in stack is not an address to the heap!");
+    }
+    
+    //Constructing the stack to give the new thread
+    new_thread_stack.push(closure_address.clone());
+    new_thread_stack.push(Val::Vunit);
+    //ret_fp
+    new_thread_stack.push(Val::Vloc(program_state.fp));
+    //ret_pc force a halt on "returning"
+    let halt_location = halt_location.expect("SPAWN: the program has no halt for the new thread to return to");
+    new_thread_stack.push(Val::Vloc(halt_location));
+
+    //Giving the new thread a copy of the instruction list
+    new_thread_program = program_state.program.clone();
+
+    State {halt: false, parked: None, pc: funptr, fp: 0, stack: new_thread_stack, program: new_thread_program}
+}
+
+fn mutex(handle: &Val, instr: &str, shared: &Shared) -> usize {
+    match handle {
+        Val::Vmutex(m) if (*m as usize) < shared.mutexes.len() => *m as usize,
+        _ => panic!("{}: Value in stack is not a mutex", instr),
+    }
+}
+
+fn instr(vector_of_states: &mut Vec<State>, heap: &mut Vec<Val>, collector: &mut dyn Collector, shared: &mut Shared, program_size: u32, thread_number: usize, config: &Config) -> Result<(), RunError> {
+
+    let mut program_state  = &mut vector_of_states[thread_number];
+        program_state.pc = program_state.pc + 1;
+        if program_state.pc - 1 < program_size {
+            match program_state.program.get((program_state.pc - 1) as usize).unwrap() {
+//PUSH
+                Instr::Push(val) => {program_state.stack.push(val.clone())},
+//POP
+                Instr::Pop => {program_state.stack.pop();},
+//PEEK
+                Instr::Peek(u) => {
+                    let peek_val = program_state.stack.get(*u as usize).unwrap();
+                    program_state.stack.push(peek_val.clone())},
+//UNARY
+                Instr::Unary(unop) => {
+                    match unop {
+                        Unop::Neg => {
+                            let op1 = program_state.stack.pop().unwrap();
+                            match op1 {
+                                Val::Vbool(b) => {
+                                        program_state.stack.push(Val::Vbool(!b));},
+                                _ => panic!("UNOP: Cannot perform negation not on a boolean"),
+                            }},
+                    }},
+//BINARY
+                Instr::Binary(binop) => {
+                    match binop {
+                        Binop::Add => {
+                            let op1 = program_state.stack.pop().unwrap();
+                            let op2 = program_state.stack.pop().unwrap();
+                            match op1 {
+                                Val::Vi32(o1) => {
+                                    match op2 {
+                                        Val::Vi32(o2) => {program_state.stack.push(Val::Vi32(o1 + o2))},
+                                        _ => panic!("ADD: Cannot perform addition on value not i32"),
+                                    }},
+                                _ => panic!("ADD: Cannot perform addition on value not i32"),
+                            }},
+                        Binop::Mul => {
+                            let op1 = program_state.stack.pop().unwrap();
+                            let op2 = program_state.stack.pop().unwrap();
+                            match op1 {
+                                Val::Vi32(o1) => {
+                                    match op2 {
+                                        Val::Vi32(o2) => {program_state.stack.push(Val::Vi32(o1 * o2))},
+                                        _ => panic!("MUL: Cannot perform multiplication on value not i32"),
+                                    }},
+                                _ => panic!("MUL: Cannot perform multiplication on value not i32"),
+                            }},
+                        Binop::Sub => {
+                            let op1 = program_state.stack.pop().unwrap();
+                            let op2 = program_state.stack.pop().unwrap();
+                            match op1 {
+                                Val::Vi32(o1) => {
+                                    match op2 {
+                                        Val::Vi32(o2) => {program_state.stack.push(Val::Vi32(o1 - o2))},
+                                        _ => panic!("SUB: Cannot perform subtraction on value not i32"),
+                                    }},
+                                _ => panic!("SUB: Cannot perform subtraction on value not i32"),
+                            }},
+                        Binop::Div => {
+                            let op1 = program_state.stack.pop().unwrap();
+                            let op2 = program_state.stack.pop().unwrap();
+                            match op1 {
+                                Val::Vi32(o1) => {
+                                    match op2 {
+                                        Val::Vi32(o2) => {
+                                            if o2 != 0 {
+                                                program_state.stack.push(Val::Vi32(o1 / o2))
+                                            }else {panic!("DIV: Cannot divide by zero!");}},
+                                        _ => panic!("DIV: Cannot perform division on value not i32"),
+                                    }},
+                                _ => panic!("DIV: Cannot perform division on value not i32"),
+                            }},
+                        Binop::Lt => {
+                            let op1 = program_state.stack.pop().unwrap();
+                            let op2 = program_state.stack.pop().unwrap();
+                            match op1 {
+                                Val::Vi32(o1) => {
+                                    match op2 {
+                                        Val::Vi32(o2) => {
+                                            if o1 < o2 {
+                                                program_state.stack.push(Val::Vbool(true));
+                                            }else {
+                                                program_state.stack.push(Val::Vbool(false));
+                                            }},
+                                        _ => panic!("LT: Cannot perform less than on values not i32"),
+                                    }},
+                                _ => panic!("LT: Cannot perform less than on values not i32"),
+                            }},
+                        Binop::Eq => {
+                            let op1 = program_state.stack.pop().unwrap();
+                            let op2 = program_state.stack.pop().unwrap();
+                            match op1 {
+                                Val::Vi32(o1) => {
+                                    match op2 {
+                                        Val::Vi32(o2) => {
+                                            if o1 == o2 {
+                                                program_state.stack.push(Val::Vbool(true));
+                                            }else {
+                                                program_state.stack.push(Val::Vbool(false));
+                                            }},
+                                        _ => panic!("EQ: Cannot perform equality on values not i32"),
+                                    }},
+                                _ => panic!("EQ: Cannot perform equality on values not i32"),
+                            }},
+                    }},
+//SWAP
+                Instr::Swap => {
+                    let v1 = program_state.stack.pop().unwrap();
+                    let v2 = program_state.stack.pop().unwrap();
+                    program_state.stack.push(v1);
+                    program_state.stack.push(v2);},
+//ALLOC
+                Instr::Alloc => {
+                    let mut unit = program_state.stack.pop().unwrap();
+                    let heap_size = program_state.stack.pop().unwrap();
+                    if let Val::Vi32(size) = heap_size {
+                        let mut pause = 0;
+                        //Asked first either way, the collector may keep track of what alloc wants
+                        let due = collector.must_collect(heap, size as u32, shared.heap_size);
+                        if due || config.stress {
+                            //The array every cell starts out as is a root too, it is off the stack already
+                            let request = Request { thread: thread_number, size: size as u32, due };
+                            pause += collect_garbage(collector, heap, vector_of_states, shared, Some(&mut unit), request, config)?;
+                        }
+                        let addr = collector.place(heap, size, &unit);
+                        vector_of_states[thread_number].stack.push(Val::Vaddr(addr));
+                        pause += collector.step(heap);
+                        shared.stats.allocated(heap, pause);
+                    }
+                },
+//SET
+                Instr::Set => {
+                    let heap_val = program_state.stack.pop().unwrap();
+                    let val_idx = program_state.stack.pop().unwrap();
+                    let val_base = program_state.stack.pop().unwrap();
+                    match val_idx {
+                        Val::Vi32(idx) => {
+                            match val_base {
+                                Val::Vaddr(base) => {
+                                    if (base as i32) + idx < heap.len() as i32 {
+                                        let old = heap.remove(base + (idx as usize) + 1);
+                                        heap.insert(base + (idx as usize) + 1, heap_val.clone());
+                                        collector.stored(heap, base + (idx as usize) + 1, &old);
+                                    }else{
+                                        panic!("SET: base + idx larger than heap");
+                                    }},
+                                _ => panic!("SET: Not a valid address for heap"),
+                            }},
+                        _ => panic!("SET: Not a valid value for heap index"),
+                    }},
+//GET
+                Instr::Get => {
+                    let val_idx = program_state.stack.pop().unwrap();
+                    let val_base = program_state.stack.pop().unwrap();
+                    match val_idx {
+                        Val::Vi32(idx) => {
+                            match val_base {
+                                Val::Vaddr(base) => {
+                                    if base + (idx as usize) < heap.len() {
+                                        let heap_val = heap.get(base + (idx as usize) + 1).unwrap();
+                                        program_state.stack.push(heap_val.clone());
+                                    }else{
+                                        panic!("GET: base + idx is larger than the heap");
+                                    }},
+                                _ => panic!("GET: Not a valid address to GET value from heap"),
+                            }},
+                        _ => panic!("GET: Cannot GET value at non i32 index on heat"),
+                    }},
+//VAR
+                Instr::Var(u) => {
+                    if (program_state.fp + *u) < program_state.stack.len() as u32 {
+                        let nvar = program_state.stack.get((program_state.fp + *u) as usize).unwrap();
+                        program_state.stack.push(nvar.clone());
+                    }else {
+                        panic!("VAR: Var value is longer than the stack length!");
+                    }},
+//STORE
+                Instr::Store(u) => {
+                    let vnew = program_state.stack.pop().unwrap();
+                    let index = (program_state.fp + u) as usize;
+                    if (index as u32) <= (program_state.stack.len() as u32){
+                        program_state.stack.remove(index);
+                        program_state.stack.insert(index, vnew.clone());
+                    }else {
+                        panic!("STORE: index larger than size of stack!");
+                    }},
+//SETFRAME
+                Instr::SetFrame(u) => {
+                    program_state.stack.push(Val::Vloc(program_state.fp));
+                    program_state.fp = (program_state.stack.len() - (*u as usize) - 1) as u32;
+                    },
+//CALL
+                Instr::Call => {
+                    let target_addr = program_state.stack.pop().unwrap();
+                    program_state.stack.push(Val::Vloc(program_state.pc));
+                    match target_addr {
+                        Val::Vloc(u) => program_state.pc = u,
+                        _ => panic!("CALL: Invalid location on Call operation"),
+                    }},
+//RET
+                Instr::Ret => {
+                    let vret = program_state.stack.pop().unwrap();
+                    let caller_pc = program_state.stack.pop().unwrap();
+                    let caller_fp = program_state.stack.pop().unwrap();
+                    match caller_pc{
+                        Val::Vloc(pc) => {
+                            match caller_fp {
+                                Val::Vloc(fp) => {
+                                    program_state.stack.truncate((program_state.fp) as usize);
+                                    program_state.fp = fp;
+                                    program_state.pc = pc;
+                                    program_state.stack.push(vret);},
+                                _ => panic!("RET: Cannot return fp to a non location"),
+                        }},
+                        _ => panic!("RET: Cannot return pc to a non location"),
+                    }},
+//BRANCH
+                Instr::Branch => {
+                    let target_location = program_state.stack.pop().unwrap();
+                    let condition = program_state.stack.pop().unwrap();
+                    match target_location {
+                        Val::Vloc(loc) => {
+                            if loc < (program_state.program.len() as u32){
+                                match condition {
+                                    Val::Vbool(b) => {
+                                    if b {
+                                        program_state.pc = loc;
+                                    }},
+                                    _ => panic!("BRANCH: Not a valid condition for branching"),
+                                }
+                            }else {
+                                panic!("BRANCH: Not a valid address to branch to");
+                            }},
+                        _ => panic!("BRANCH: Not a value to branch to"),
+                    }},
+//HALT
+                Instr::Halt => {
+                    program_state.halt = true;},
+//PRINT                
+                Instr::Print => {
+                    let val_to_be_print = program_state.stack.pop().unwrap();
+                    match val_to_be_print {
+                        Val::Vi32(int) => {
+                            print!("{}", char::from_u32(int as u32).unwrap());},
+                        _ => {panic!("PRINT: Cannot print out values that are not I32");},
+                    }},
+//SPAWN, leaving a handle to the new thread for JOIN
+                Instr::Spawn => {
+                    let closure_address = program_state.stack.pop().unwrap();
+                    let new_thread = spawned(closure_address, heap, program_state, shared.halt_location);
+
+                    let handle = Val::Vthread(vector_of_states.len() as u32);
+                    vector_of_states[thread_number].stack.push(handle);
+                    vector_of_states.push(new_thread);
+                    
+
+                },
+//JOIN
+                Instr::Join => {
+                    let handle = program_state.stack.pop().unwrap();
+                    let thread = match handle {
+                        Val::Vthread(thread) => thread as usize,
+                        _ => panic!("JOIN: Value in stack is not a thread handle"),
+                    };
+                    //A halted thread returned to the halt, which left its return value on top of its stack
+                    let result = match vector_of_states.get(thread) {
+                        Some(target) if target.halt => Some(target.stack.last().cloned().unwrap_or(Val::Vunit)),
+                        Some(_) => None,
+                        None => panic!("JOIN: There is no thread {}", thread),
+                    };
+                    let program_state = &mut vector_of_states[thread_number];
+                    match result {
+                        Some(val) => program_state.stack.push(val),
+                        None => {
+                            program_state.stack.push(handle);
+                            program_state.pc -= 1;
+                            program_state.parked = Some(Wait::Join(thread));
+                        },
+                    }
+                },
+//CHAN
+                Instr::Chan => {
+                    program_state.stack.push(Val::Vchan(shared.channels.len() as u32));
+                    shared.channels.push(Channel::default());
+                },
+//SEND
+                Instr::Send => {
+                    let val = program_state.stack.pop().unwrap();
+                    let channel = channel(program_state.stack.pop().unwrap(), "SEND", shared);
+                    if channel.closed {
+                        panic!("SEND: Cannot send on a closed channel");
+                    }
+                    channel.queue.push_back(val);
+                },
+//RECV, parking until there is a value or the channel is closed, which gives undef
+                Instr::Recv => {
+                    let handle = program_state.stack.pop().unwrap();
+                    let channel = channel(handle.clone(), "RECV", shared);
+                    match channel.queue.pop_front() {
+                        Some(val) => program_state.stack.push(val),
+                        None if channel.closed => program_state.stack.push(Val::Vundef),
+                        None => {
+                            program_state.stack.push(handle.clone());
+                            program_state.pc -= 1;
+                            if let Val::Vchan(c) = handle {
+                                program_state.parked = Some(Wait::Recv(c as usize));
+                            }
+                        },
+                    }
+                },
+//CLOSE
+                Instr::Close => {
+                    let channel = channel(program_state.stack.pop().unwrap(), "CLOSE", shared);
+                    if channel.closed {
+                        panic!("CLOSE: Channel is already closed");
+                    }
+                    channel.closed = true;
+                },
+//MUTEX
+                Instr::Mutex => {
+                    program_state.stack.push(Val::Vmutex(shared.mutexes.len() as u32));
+                    shared.mutexes.push(None);
+                },
+//ACQUIRE, parking until the mutex is free
+                Instr::Acquire => {
+                    let handle = program_state.stack.pop().unwrap();
+                    let m = mutex(&handle, "ACQUIRE", shared);
+                    match shared.mutexes[m] {
+                        None => shared.mutexes[m] = Some(thread_number),
+                        Some(_) => {
+                            program_state.stack.push(handle);
+                            program_state.pc -= 1;
+                            program_state.parked = Some(Wait::Acquire(m));
+                        },
+                    }
+                },
+//RELEASE
+                Instr::Release => {
+                    let m = mutex(&program_state.stack.pop().unwrap(), "RELEASE", shared);
+                    if shared.mutexes[m] != Some(thread_number) {
+                        panic!("RELEASE: Thread {} does not hold mutex {}", thread_number, m);
+                    }
+                    shared.mutexes[m] = None;
+                },
+//CAS, setting base[idx] to the new value only if it still holds the expected one
+                Instr::Cas => {
+                    let new_val = program_state.stack.pop().unwrap();
+                    let expected = program_state.stack.pop().unwrap();
+                    let val_idx = program_state.stack.pop().unwrap();
+                    let val_base = program_state.stack.pop().unwrap();
+                    match (val_base, val_idx) {
+                        (Val::Vaddr(base), Val::Vi32(idx)) => {
+                            let cell = base + (idx as usize) + 1;
+                            if cell >= heap.len() {
+                                panic!("CAS: base + idx larger than heap");
+                            }
+                            let swapped = heap[cell] == expected;
+                            if swapped {
+                                let old = mem::replace(&mut heap[cell], new_val);
+                                collector.stored(heap, cell, &old);
+                            }
+                            program_state.stack.push(Val::Vbool(swapped));
+                        },
+                        (Val::Vaddr(_), _) => panic!("CAS: Not a valid value for heap index"),
+                        _ => panic!("CAS: Not a valid address for heap"),
+                    }
+                },
+//LABEL
+                Instr::Label(label) => panic!("LABEL: {} is never part of an assembled program", label),
+            }
+        }else {panic!("MAIN: PC is greater than program length!");}
+    Ok(())
+}
+
+//Where a spawned thread returns to, so that it halts
+fn halt_location(stack_instr: &[Instr]) -> Option<u32> {
+    stack_instr.iter().position(|inst| *inst == Instr::Halt).map(|pc| pc as u32)
+}
+
+/// Runs `program` until its main thread halts and returns the value that
+/// thread left on top of its stack. Gives up with a deadlock when no thread
+/// can run, or when main waits on threads that wait on it in turn or on a
+/// mutex that a halted thread still holds.
+pub fn run(program: Vec<Instr>, config: &Config) -> Result<Val, RunError> {
+    run_counted(program, config).0
+}
+
+/// Runs `program` like run() and also gives back the stats of every
+/// collection, however the run ended, so the caller can print their
+/// summary once it has reported the outcome.
+pub fn run_counted(program: Vec<Instr>, config: &Config) -> (Result<Val, RunError>, Stats) {
+    let stats = match open_stats(config) {
+        Ok(stats) => stats,
+        Err(err) => return (Err(err), Stats::default()),
+    };
+    if config.backend == Backend::Os {
+        return parallel::run(program, config, stats);
+    }
+    //A thread that runs no instructions at a time would never get anywhere
+    if config.quantum == 0 {
+        return (Err(RunError::Failed("the quantum must be at least 1".to_string())), stats);
+    }
+    let mut shared = Shared { heap_size: config.heap_size, stats, halt_location: halt_location(&program), ..Shared::default() };
+    let outcome = run_green(program, &mut shared, config);
+    report(shared.stats, outcome)
+}
+
+fn run_green(program: Vec<Instr>, shared: &mut Shared, config: &Config) -> Result<Val, RunError> {
+    let stack_instr = program;
+    let program_size = stack_instr.len() as u32;
+    let program_stack: Vec<Val> = Vec::new();
+    //One heap for every thread, so threads can share arrays
+    let mut heap: Vec<Val> = Vec::new();
+
+    let mut scheduler = config.policy.scheduler(config.quantum);
+    let mut collector = config.collector.collector();
+    let mut thread_states: Vec<State> = Vec::new();
+    let mut executed: u64 = 0;
+
+    let main_program_state = State { halt: false, parked: None, pc: 0, fp: 0, stack: program_stack, program: stack_instr};
+   
+    thread_states.push(main_program_state);
+
+    //change to while gc state does not halt
+    while !thread_states[0].halt{
+        //A parked thread sits out until what it waits for has happened
+        for index_of_thread in 0..thread_states.len() {
+            if let Some(wait) = thread_states[index_of_thread].parked {
+                if shared.ready(wait, &thread_states) {
+                    thread_states[index_of_thread].parked = None;
+                }
+            }
+        }
+        let runnable: Vec<usize> = (0..thread_states.len())
+            .filter(|&thread| !thread_states[thread].halt && thread_states[thread].parked.is_none())
+            .collect();
+        if runnable.is_empty() || shared.stuck(0, &thread_states) {
+            let threads = (0..thread_states.len()).map(|thread| shared.describe(thread, &thread_states)).collect();
+            return Err(RunError::Deadlock(threads));
+        }
+
+        let (index_of_thread, slice) = scheduler.pick(&runnable, thread_states.len());
+        for _number_of_exec in 0..slice {
+            if config.fuel == Some(executed) {
+                return Err(RunError::Failed(format!("out of fuel after {} instructions", executed)));
+            }
+            executed += 1;
+            if config.trace {
+                let state = &thread_states[index_of_thread];
+                if let Some(next) = state.program.get(state.pc as usize) {
+                    eprintln!("t{} {:>5}: {}", index_of_thread, state.pc, next);
+                }
+            }
+            instr(&mut thread_states, &mut heap, &mut *collector, shared, program_size, index_of_thread, config)?;
+            //A thread that parks or halts gives up the rest of its slice
+            if thread_states[index_of_thread].parked.is_some() || thread_states[index_of_thread].halt {
+                break;
+            }
+        }
+    }//While-loop
+
+    thread_states[0].stack.pop().ok_or_else(|| RunError::Failed("HALT: the stack is empty".to_string()))
+}
@@ -0,0 +1,388 @@
+//! Runs every spawned thread on its own OS thread instead of taking turns
+//! on one. Instructions that only touch a thread's own stack run without
+//! any locking; the heap, channels and mutexes live in one `World` behind a
+//! mutex, and a thread that has to wait for something sleeps on a condition
+//! variable until another thread changes the world. That one mutex means
+//! get, set and alloc run one thread at a time, so only the work threads do
+//! on their own stacks runs on every core at once.
+//!
+//! A collection stops the world. The thread that runs out of heap sets
+//! `stop_the_world` and waits; every other thread checks `interrupt` before
+//! each instruction and, when it is set, puts its state into the world and
+//! sleeps until the collection is over. Parked and halted threads have
+//! their state in the world already, so once no thread has its state out
+//! every stack can be used as a root.
+
+use std::any::Any;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread::{self, Scope};
+
+use grumpy_isa::{Instr, Val};
+
+use crate::{channel, collect_garbage, instr, mutex, report, halt_location, spawned};
+use crate::stats::Stats;
+use crate::collector::{Collector, Copying};
+use crate::{Config, Request, RunError, Shared, State, Wait};
+
+/// What the threads share. `threads[t]` is thread t's state while it is not
+/// running and a placeholder while it is, `running` says which.
+struct World {
+    heap: Vec<Val>,
+    collector: Box<dyn Collector + Send>,
+    shared: Shared,
+    threads: Vec<State>,
+    running: Vec<bool>,
+    stop_the_world: bool,
+    outcome: Option<Result<Val, RunError>>,
+}
+
+struct Machine {
+    world: Mutex<World>,
+    changed: Condvar,
+    //Set while the world is stopped or once the run is over, so a running thread knows to look at the world
+    interrupt: AtomicBool,
+    executed: AtomicU64,
+    program_size: u32,
+    config: Config,
+}
+
+impl Machine {
+    //A panic in one thread leaves the lock poisoned, the world is still fine for the others to see the run is over
+    fn lock(&self) -> MutexGuard<'_, World> {
+        self.world.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn wait<'a>(&self, world: MutexGuard<'a, World>) -> MutexGuard<'a, World> {
+        self.changed.wait(world).unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn finish(&self, world: &mut World, outcome: Result<Val, RunError>) {
+        if world.outcome.is_none() {
+            world.outcome = Some(outcome);
+        }
+        self.interrupt.store(true, Ordering::Release);
+        self.changed.notify_all();
+    }
+
+    fn put_away(&self, world: &mut World, thread: usize, state: &mut State) {
+        world.threads[thread] = mem::take(state);
+        world.running[thread] = false;
+        self.changed.notify_all();
+    }
+
+    fn take_back(&self, world: &mut World, thread: usize, state: &mut State) {
+        *state = mem::take(&mut world.threads[thread]);
+        world.running[thread] = true;
+    }
+
+    //Locks the world for a running thread, first sitting out a collection if one is going on. None once the run is over.
+    fn enter(&self, thread: usize, state: &mut State) -> Option<MutexGuard<'_, World>> {
+        let mut world = self.lock();
+        if world.stop_the_world && world.outcome.is_none() {
+            self.put_away(&mut world, thread, state);
+            while world.stop_the_world && world.outcome.is_none() {
+                world = self.wait(world);
+            }
+            self.take_back(&mut world, thread, state);
+        }
+        match world.outcome {
+            Some(_) => None,
+            None => Some(world),
+        }
+    }
+
+    //Stops every other thread, collects with all their stacks as roots, and lets them go again, returning the pause
+    fn collect<'a>(&'a self, mut world: MutexGuard<'a, World>, state: &mut State, request: Request) -> Option<(MutexGuard<'a, World>, u64)> {
+        let thread = request.thread;
+        world.stop_the_world = true;
+        self.interrupt.store(true, Ordering::Release);
+        self.put_away(&mut world, thread, state);
+        while world.running.iter().any(|&running| running) && world.outcome.is_none() {
+            world = self.wait(world);
+        }
+        if world.outcome.is_some() {
+            return None;
+        }
+        let pause = {
+            let World { heap, collector, shared, threads, .. } = &mut *world;
+            collect_garbage(&mut **collector, heap, threads, shared, None, request, &self.config)
+        };
+        let pause = match pause {
+            Ok(pause) => pause,
+            Err(err) => {
+                self.finish(&mut world, Err(err));
+                return None;
+            },
+        };
+        world.stop_the_world = false;
+        self.interrupt.store(false, Ordering::Release);
+        self.changed.notify_all();
+        self.take_back(&mut world, thread, state);
+        Some((world, pause))
+    }
+
+    //Parks the thread on the instruction it is at until what it waits for has happened
+    fn block<'a>(&'a self, mut world: MutexGuard<'a, World>, thread: usize, state: &mut State, wait: Wait) -> Option<MutexGuard<'a, World>> {
+        state.parked = Some(wait);
+        self.put_away(&mut world, thread, state);
+        self.check_deadlock(&mut world);
+        loop {
+            if world.outcome.is_some() {
+                return None;
+            }
+            if !world.stop_the_world && world.shared.ready(wait, &world.threads) {
+                break;
+            }
+            world = self.wait(world);
+        }
+        self.take_back(&mut world, thread, state);
+        state.parked = None;
+        Some(world)
+    }
+
+    //The same deadlocks as the green threads find: nothing left that can run, or main waiting on something that
+    //can never happen
+    fn check_deadlock(&self, world: &mut World) {
+        let World { shared, threads, running, .. } = &*world;
+        if threads[0].halt {
+            return;
+        }
+        let waiting = |thread: usize| match threads[thread].parked {
+            Some(wait) => !shared.ready(wait, threads),
+            None => false,
+        };
+        let nothing_runs = (0..threads.len()).all(|thread| !running[thread] && (threads[thread].halt || waiting(thread)));
+        if nothing_runs || shared.stuck(0, threads) {
+            let report = (0..threads.len())
+                .map(|thread| if running[thread] { format!("thread {} running", thread) } else { shared.describe(thread, threads) })
+                .collect();
+            self.finish(world, Err(RunError::Deadlock(report)));
+        }
+    }
+}
+
+//What a panic said, when it said it with a string
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(msg) => msg,
+        None => payload.downcast_ref::<String>().map_or("", |msg| msg.as_str()),
+    }
+}
+
+//A thread that panics ends the run with an error, so that the others stop instead of waiting for it forever, and the
+//panic never reaches thread::scope, which would panic again
+fn run_caught<'scope, 'env>(machine: &'env Machine, scope: &'scope Scope<'scope, 'env>, id: usize, state: State) {
+    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| run_thread(machine, scope, id, state))) {
+        let mut world = machine.lock();
+        machine.finish(&mut world, Err(RunError::Failed(format!("thread {} panicked: {}", id, panic_message(&*payload)))));
+    }
+}
+
+fn run_thread<'scope, 'env>(machine: &'env Machine, scope: &'scope Scope<'scope, 'env>, id: usize, state: State) {
+    //instr() runs an instruction of one of a list of threads, here the list is just this one
+    let mut local = vec![state];
+    loop {
+        if machine.interrupt.load(Ordering::Acquire) && machine.enter(id, &mut local[0]).is_none() {
+            return;
+        }
+        if let Some(fuel) = machine.config.fuel {
+            if machine.executed.fetch_add(1, Ordering::Relaxed) >= fuel {
+                let mut world = machine.lock();
+                machine.finish(&mut world, Err(RunError::Failed(format!("out of fuel after {} instructions", fuel))));
+                return;
+            }
+        }
+        let state = &local[0];
+        let next = match state.program.get(state.pc as usize) {
+            Some(next) => next,
+            None => panic!("MAIN: PC is greater than program length!"),
+        };
+        if machine.config.trace {
+            eprintln!("t{} {:>5}: {}", id, state.pc, next);
+        }
+        match next {
+            Instr::Alloc | Instr::Get | Instr::Set | Instr::Cas | Instr::Spawn | Instr::Join | Instr::Chan
+            | Instr::Send | Instr::Recv | Instr::Close | Instr::Mutex | Instr::Acquire | Instr::Release
+            | Instr::Halt => {
+                let next = next.clone();
+                if !shared_instr(machine, scope, id, &mut local, &next) {
+                    return;
+                }
+            },
+            //Only alloc can fail, and it is never run here
+            _ => instr(&mut local, &mut Vec::new(), &mut Copying, &mut Shared::default(), machine.program_size, 0, &machine.config)
+                .expect("an instruction that does not touch the heap failed"),
+        }
+    }
+}
+
+//Runs an instruction that needs the world, false once the thread is done
+fn shared_instr<'scope, 'env>(machine: &'env Machine, scope: &'scope Scope<'scope, 'env>, id: usize, local: &mut Vec<State>, next: &Instr) -> bool {
+    let mut world = match machine.enter(id, &mut local[0]) {
+        Some(world) => world,
+        None => return false,
+    };
+    match next {
+        Instr::Alloc => {
+            let size = match local[0].stack.len().checked_sub(2).map(|under| &local[0].stack[under]) {
+                Some(Val::Vi32(size)) => *size,
+                //Leave anything else to alloc as the green threads do
+                _ => {
+                    let World { heap, collector, shared, .. } = &mut *world;
+                    return match instr(local, heap, &mut **collector, shared, machine.program_size, 0, &machine.config) {
+                        Ok(()) => true,
+                        Err(err) => {
+                            machine.finish(&mut world, Err(err));
+                            false
+                        },
+                    };
+                },
+            };
+            let mut pause = 0;
+            let World { heap, collector, shared, .. } = &mut *world;
+            let due = collector.must_collect(heap, size as u32, shared.heap_size);
+            if due || machine.config.stress {
+                let request = Request { thread: id, size: size as u32, due };
+                world = match machine.collect(world, &mut local[0], request) {
+                    Some((world, collected)) => {
+                        pause += collected;
+                        world
+                    },
+                    None => return false,
+                };
+            }
+            let state = &mut local[0];
+            let unit = state.stack.pop().unwrap();
+            state.stack.pop();
+            let World { heap, collector, shared, .. } = &mut *world;
+            state.stack.push(Val::Vaddr(collector.place(heap, size, &unit)));
+            pause += collector.step(heap);
+            shared.stats.allocated(heap, pause);
+            state.pc += 1;
+        },
+        Instr::Get | Instr::Set | Instr::Cas | Instr::Chan | Instr::Send | Instr::Close => {
+            let World { heap, collector, shared, .. } = &mut *world;
+            if let Err(err) = instr(local, heap, &mut **collector, shared, machine.program_size, 0, &machine.config) {
+                machine.finish(&mut world, Err(err));
+                return false;
+            }
+            //A thread may be waiting on the channel
+            if let Instr::Send | Instr::Close = next {
+                machine.changed.notify_all();
+            }
+        },
+        Instr::Spawn => {
+            let state = &mut local[0];
+            let closure_address = state.stack.pop().unwrap();
+            let new_thread = spawned(closure_address, &world.heap, state, world.shared.halt_location);
+            let new_id = world.threads.len();
+            world.threads.push(State::default());
+            world.running.push(true);
+            state.stack.push(Val::Vthread(new_id as u32));
+            state.pc += 1;
+            drop(world);
+            scope.spawn(move || run_caught(machine, scope, new_id, new_thread));
+        },
+        Instr::Join => {
+            let thread = match local[0].stack.last() {
+                Some(Val::Vthread(thread)) => *thread as usize,
+                _ => panic!("JOIN: Value in stack is not a thread handle"),
+            };
+            match world.threads.get(thread) {
+                Some(target) if target.halt => {
+                    let result = target.stack.last().cloned().unwrap_or(Val::Vunit);
+                    let state = &mut local[0];
+                    state.stack.pop();
+                    state.stack.push(result);
+                    state.pc += 1;
+                },
+                Some(_) => return machine.block(world, id, &mut local[0], Wait::Join(thread)).is_some(),
+                None => panic!("JOIN: There is no thread {}", thread),
+            }
+        },
+        Instr::Recv => {
+            let handle = local[0].stack.last().cloned().unwrap();
+            let channel = channel(handle.clone(), "RECV", &mut world.shared);
+            let received = match channel.queue.pop_front() {
+                Some(val) => val,
+                None if channel.closed => Val::Vundef,
+                None => match handle {
+                    Val::Vchan(c) => return machine.block(world, id, &mut local[0], Wait::Recv(c as usize)).is_some(),
+                    _ => unreachable!("channel() only gives back the channel of a Vchan"),
+                },
+            };
+            let state = &mut local[0];
+            state.stack.pop();
+            state.stack.push(received);
+            state.pc += 1;
+        },
+        Instr::Mutex => {
+            let state = &mut local[0];
+            state.stack.push(Val::Vmutex(world.shared.mutexes.len() as u32));
+            world.shared.mutexes.push(None);
+            state.pc += 1;
+        },
+        Instr::Acquire => {
+            let m = mutex(local[0].stack.last().unwrap(), "ACQUIRE", &world.shared);
+            if world.shared.mutexes[m].is_some() {
+                return machine.block(world, id, &mut local[0], Wait::Acquire(m)).is_some();
+            }
+            world.shared.mutexes[m] = Some(id);
+            local[0].stack.pop();
+            local[0].pc += 1;
+        },
+        Instr::Release => {
+            let m = mutex(&local[0].stack.pop().unwrap(), "RELEASE", &world.shared);
+            if world.shared.mutexes[m] != Some(id) {
+                panic!("RELEASE: Thread {} does not hold mutex {}", id, m);
+            }
+            world.shared.mutexes[m] = None;
+            local[0].pc += 1;
+            machine.changed.notify_all();
+        },
+        Instr::Halt => {
+            let state = &mut local[0];
+            state.halt = true;
+            state.pc += 1;
+            if id == 0 {
+                let result = state.stack.pop().ok_or_else(|| RunError::Failed("HALT: the stack is empty".to_string()));
+                machine.finish(&mut world, result);
+            } else {
+                machine.put_away(&mut world, id, state);
+                machine.check_deadlock(&mut world);
+            }
+            return false;
+        },
+        other => unreachable!("{} does not need the world", other),
+    }
+    true
+}
+
+/// Runs `program` like `run_counted()`, but with every thread on an OS
+/// thread of its own. The scheduler policy and quantum do not apply.
+pub(crate) fn run(program: Vec<Instr>, config: &Config, stats: Stats) -> (Result<Val, RunError>, Stats) {
+    let machine = Machine {
+        world: Mutex::new(World {
+            heap: Vec::new(),
+            collector: config.collector.collector(),
+            shared: Shared { heap_size: config.heap_size, stats, halt_location: halt_location(&program), ..Shared::default() },
+            threads: vec![State::default()],
+            running: vec![true],
+            stop_the_world: false,
+            outcome: None,
+        }),
+        changed: Condvar::new(),
+        interrupt: AtomicBool::new(false),
+        executed: AtomicU64::new(0),
+        program_size: program.len() as u32,
+        config: config.clone(),
+    };
+    let main = State { halt: false, parked: None, pc: 0, fp: 0, stack: Vec::new(), program };
+    thread::scope(|scope| run_caught(&machine, scope, 0, main));
+    let mut world = machine.world.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let outcome = world.outcome.take().unwrap_or_else(|| Err(RunError::Failed("main never halted".to_string())));
+    report(mem::take(&mut world.shared.stats), outcome)
+}
@@ -0,0 +1,126 @@
+//! Which thread runs next. run() hands the scheduler the threads that can
+//! run and runs the one it picks until its slice is used up, it parks or it
+//! halts, then asks again.
+
+use std::collections::VecDeque;
+
+pub const QUANTUM: u32 = 3;
+
+pub trait Scheduler {
+    /// Picks one of `runnable`, which is never empty and in thread order,
+    /// and how many instructions it runs. `threads` is how many threads
+    /// have been spawned so far, counting those that halted.
+    fn pick(&mut self, runnable: &[usize], threads: usize) -> (usize, u32);
+}
+
+/// How a program's threads are scheduled.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Policy {
+    /// Every thread in turn, as the VM always did.
+    RoundRobin,
+    /// A random thread for a random part of the quantum, the same ones every
+    /// time for the same seed.
+    Random(u64),
+    /// The runnable thread with the highest priority, taking turns with those
+    /// that have the same priority. Thread i has the i-th priority, threads
+    /// past the end of the list have priority 0.
+    Priority(Vec<u32>),
+}
+
+impl Policy {
+    pub fn scheduler(&self, quantum: u32) -> Box<dyn Scheduler> {
+        match self {
+            Policy::RoundRobin => Box::new(RoundRobin::new(quantum)),
+            Policy::Random(seed) => Box::new(Random::new(quantum, *seed)),
+            Policy::Priority(priorities) => Box::new(Priority::new(quantum, priorities.clone())),
+        }
+    }
+}
+
+/// Goes through the threads in rounds. A round is every thread there was
+/// when it started, so a thread spawned during a round first runs in the
+/// next one, and a thread that is woken before its place in the round comes
+/// up still gets its turn.
+pub struct RoundRobin {
+    quantum: u32,
+    round: VecDeque<usize>,
+}
+
+impl RoundRobin {
+    pub fn new(quantum: u32) -> RoundRobin {
+        RoundRobin { quantum, round: VecDeque::new() }
+    }
+}
+
+impl Scheduler for RoundRobin {
+    fn pick(&mut self, runnable: &[usize], threads: usize) -> (usize, u32) {
+        loop {
+            while let Some(thread) = self.round.pop_front() {
+                if runnable.contains(&thread) {
+                    return (thread, self.quantum);
+                }
+            }
+            self.round.extend(0..threads);
+        }
+    }
+}
+
+/// Preempts at random. The generator is xorshift64*, which is plenty for
+/// shuffling threads and keeps the VM free of dependencies.
+pub struct Random {
+    quantum: u32,
+    state: u64,
+}
+
+impl Random {
+    pub fn new(quantum: u32, seed: u64) -> Random {
+        //xorshift never leaves 0, so spread the seed out first
+        let state = seed.wrapping_add(0x9e37_79b9_7f4a_7c15).wrapping_mul(0xbf58_476d_1ce4_e5b9) | 1;
+        Random { quantum, state }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+impl Scheduler for Random {
+    fn pick(&mut self, runnable: &[usize], _threads: usize) -> (usize, u32) {
+        let thread = runnable[(self.next() % runnable.len() as u64) as usize];
+        let slice = 1 + (self.next() % u64::from(self.quantum.max(1))) as u32;
+        (thread, slice)
+    }
+}
+
+pub struct Priority {
+    quantum: u32,
+    priorities: Vec<u32>,
+    last: Option<usize>,
+}
+
+impl Priority {
+    pub fn new(quantum: u32, priorities: Vec<u32>) -> Priority {
+        Priority { quantum, priorities, last: None }
+    }
+
+    fn priority(&self, thread: usize) -> u32 {
+        self.priorities.get(thread).cloned().unwrap_or(0)
+    }
+}
+
+impl Scheduler for Priority {
+    fn pick(&mut self, runnable: &[usize], _threads: usize) -> (usize, u32) {
+        let highest = runnable.iter().map(|&thread| self.priority(thread)).max().unwrap();
+        let first: Vec<usize> = runnable.iter().cloned().filter(|&thread| self.priority(thread) == highest).collect();
+        //The one after the last thread that ran, so equals take turns
+        let thread = match self.last {
+            Some(last) => first.iter().cloned().find(|&thread| thread > last).unwrap_or(first[0]),
+            None => first[0],
+        };
+        self.last = Some(thread);
+        (thread, self.quantum)
+    }
+}
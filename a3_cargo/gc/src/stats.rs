@@ -0,0 +1,127 @@
+//! What the collectors did. With Config::stats set every collection is
+//! written to that file as a line of JSON, like
+//!
+//! ```text
+//! {"collection":1,"collector":"copying","kind":"full","trigger":"heap full","thread":0,"wanted":10,"heap_size":1024,
+//!  "objects_before":93,"slots_before":1019,"objects_after":3,"slots_after":33,"live_after":33,
+//!  "values_copied":33,"bytes_copied":264,"roots":[12,7],"channel_roots":0,"pause":1054,"resized_to":1024}
+//! ```
+//!
+//! (on one line). `kind` is "minor" or "major" for the generational
+//! collector and "cycle started" or "cycle finished" for the incremental one.
+//! Objects are arrays and slots the values they take up, headers included,
+//! counted over the whole heap, and `live_after` is what the collector
+//! knows to be live, null while it cannot tell. `bytes_copied` counts every
+//! value copied as CELL_BYTES. `roots` is how many values of each thread's
+//! stack were scanned, `pause` the work the program waited for, in
+//! instructions, and `resized_to` the heap size the growth policy picked
+//! afterwards. Stats::summary() totals them for the end of a run.
+
+use grumpy_isa::Val;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// The bytes a heap value counts as in the stats: one 64-bit word, what an
+/// i32, a bool or an address needs once its tag is packed in. A Val takes
+/// more than that in this VM's memory only because Vlabel holds a String,
+/// and labels never go in the heap of an assembled program.
+pub const CELL_BYTES: u64 = 8;
+
+/// One collection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub collection: u64,
+    pub collector: &'static str,
+    pub kind: &'static str,
+    pub trigger: &'static str,
+    pub thread: usize,
+    pub wanted: u32,
+    pub heap_size: u32,
+    pub objects_before: usize,
+    pub slots_before: usize,
+    pub objects_after: usize,
+    pub slots_after: usize,
+    pub live_after: Option<usize>,
+    pub copied: u64,
+    pub roots: Vec<usize>,
+    pub channel_roots: usize,
+    pub pause: u64,
+    pub resized_to: u32,
+}
+
+impl Event {
+    pub fn to_json(&self) -> String {
+        let roots: Vec<String> = self.roots.iter().map(|roots| roots.to_string()).collect();
+        let live_after = self.live_after.map_or("null".to_string(), |live| live.to_string());
+        format!("{{\"collection\":{},\"collector\":\"{}\",\"kind\":\"{}\",\"trigger\":\"{}\",\"thread\":{},\"wanted\":{},\"heap_size\":{},\
+                 \"objects_before\":{},\"slots_before\":{},\"objects_after\":{},\"slots_after\":{},\"live_after\":{},\
+                 \"values_copied\":{},\"bytes_copied\":{},\"roots\":[{}],\"channel_roots\":{},\"pause\":{},\"resized_to\":{}}}",
+                self.collection, self.collector, self.kind, self.trigger, self.thread, self.wanted, self.heap_size,
+                self.objects_before, self.slots_before, self.objects_after, self.slots_after, live_after,
+                self.copied, self.copied * CELL_BYTES, roots.join(","), self.channel_roots, self.pause, self.resized_to)
+    }
+}
+
+/// How many arrays the heap holds.
+pub fn objects(heap: &[Val]) -> usize {
+    let mut objects = 0;
+    let mut addr = 0;
+    while addr < heap.len() {
+        addr += match heap[addr] {
+            Val::Vsize(size) => size.max(0) as usize + 1,
+            _ => 1,
+        };
+        objects += 1;
+    }
+    objects
+}
+
+/// Totals over a run, and the file the events go to.
+#[derive(Debug, Default)]
+pub struct Stats {
+    log: Option<BufWriter<File>>,
+    pub collections: u64,
+    pub copied: u64,
+    pub peak: usize,
+    pub longest_pause: u64,
+}
+
+impl Stats {
+    pub fn new(path: Option<&Path>) -> io::Result<Stats> {
+        let log = match path {
+            Some(path) => Some(BufWriter::new(File::create(path)?)),
+            None => None,
+        };
+        Ok(Stats { log, ..Stats::default() })
+    }
+
+    pub fn record(&mut self, event: &Event) -> io::Result<()> {
+        self.collections += 1;
+        self.copied += event.copied;
+        match &mut self.log {
+            Some(log) => writeln!(log, "{}", event.to_json()),
+            None => Ok(()),
+        }
+    }
+
+    /// Counts an alloc that made the program wait `pause` instructions.
+    pub fn allocated(&mut self, heap: &[Val], pause: u64) {
+        self.peak = self.peak.max(heap.len());
+        self.longest_pause = self.longest_pause.max(pause);
+    }
+
+    /// What the binaries print to stderr at exit, after the result or the error.
+    pub fn summary(&self) -> String {
+        format!("GC: {} collections, {} bytes ({} values) copied, peak heap {} values, longest pause {} instructions",
+                self.collections, self.copied * CELL_BYTES, self.copied, self.peak, self.longest_pause)
+    }
+
+    /// Makes sure every event is in the file.
+    pub fn finish(&mut self) -> io::Result<()> {
+        match &mut self.log {
+            Some(log) => log.flush(),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+push 1
+push 0
+alloc
+push 2
+push Lworker
+alloc
+var 3
+push 1
+var 2
+set
+var 3
+spawn
+var 3
+spawn
+var 4
+join
+pop
+var 5
+join
+pop
+var 2
+push 0
+get
+store 2
+pop
+pop
+pop
+ret
+Lworker:
+push 10
+push 0
+Lincr:
+var 0
+push 1
+get
+push 0
+get
+store 5
+var 0
+push 1
+get
+push 0
+var 5
+push 1
+var 5
+binary +
+cas
+unary neg
+push Lincr
+branch
+push 1
+var 4
+binary -
+store 4
+push 0
+var 4
+binary ==
+unary neg
+push Lincr
+branch
+pop
+pop
+push tt
+ret
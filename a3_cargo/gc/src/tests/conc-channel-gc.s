@@ -0,0 +1,32 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+chan
+peek 2
+push 1
+push 42
+alloc
+send
+push 300
+Lgarbage:
+push 4
+push tt
+alloc
+pop
+push 1
+var 3
+binary -
+store 3
+push 0
+var 3
+binary ==
+unary neg
+push Lgarbage
+branch
+pop
+recv
+push 0
+get
+ret
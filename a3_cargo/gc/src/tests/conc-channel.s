@@ -0,0 +1,65 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+chan
+push 2
+push Lproducer
+alloc
+peek 3
+push 1
+peek 2
+set
+peek 3
+spawn
+pop
+push 5
+push 0
+Lloop:
+var 2
+recv
+var 5
+binary +
+store 5
+push 1
+var 4
+binary -
+store 4
+push 0
+var 4
+binary ==
+unary neg
+push Lloop
+branch
+var 5
+store 2
+pop
+pop
+pop
+ret
+Lproducer:
+push 5
+Lsend:
+var 0
+push 1
+get
+var 4
+send
+push 1
+var 4
+binary -
+store 4
+push 0
+var 4
+binary ==
+unary neg
+push Lsend
+branch
+pop
+var 0
+push 1
+get
+close
+push tt
+ret
@@ -0,0 +1,10 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+chan
+peek 2
+close
+recv
+ret
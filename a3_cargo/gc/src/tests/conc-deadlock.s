@@ -0,0 +1,8 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+chan
+recv
+ret
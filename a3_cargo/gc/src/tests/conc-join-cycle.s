@@ -0,0 +1,39 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+mutex
+var 2
+acquire
+push 2
+push Lworker
+alloc
+var 3
+push 1
+var 2
+set
+var 3
+spawn
+push 1
+push Lspin
+alloc
+spawn
+pop
+var 4
+join
+store 2
+pop
+pop
+ret
+Lworker:
+var 0
+push 1
+get
+acquire
+push tt
+ret
+Lspin:
+push true
+push Lspin
+branch
@@ -0,0 +1,40 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+push 1
+push Lsix
+alloc
+spawn
+push 1
+push Lseven
+alloc
+spawn
+var 2
+join
+var 3
+join
+binary *
+store 2
+pop
+ret
+Lsix:
+push 6
+ret
+Lseven:
+push 20
+Lcount:
+push 1
+var 4
+binary -
+store 4
+push 0
+var 4
+binary ==
+unary neg
+push Lcount
+branch
+pop
+push 7
+ret
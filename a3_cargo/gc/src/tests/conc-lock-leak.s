@@ -0,0 +1,41 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+mutex
+push 2
+push Lleak
+alloc
+var 3
+push 1
+var 2
+set
+var 3
+spawn
+push 1
+push Lspin
+alloc
+spawn
+pop
+var 4
+join
+pop
+var 2
+acquire
+push 0
+store 2
+pop
+pop
+ret
+Lleak:
+var 0
+push 1
+get
+acquire
+push tt
+ret
+Lspin:
+push true
+push Lspin
+branch
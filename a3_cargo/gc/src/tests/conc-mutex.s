@@ -0,0 +1,77 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+push 1
+push 0
+alloc
+push 3
+push Lworker
+alloc
+var 3
+push 1
+var 2
+set
+var 3
+push 2
+mutex
+set
+var 3
+spawn
+var 3
+spawn
+var 4
+join
+pop
+var 5
+join
+pop
+var 2
+push 0
+get
+store 2
+pop
+pop
+pop
+ret
+Lworker:
+push 10
+push 0
+Lincr:
+var 0
+push 2
+get
+acquire
+var 0
+push 1
+get
+push 0
+get
+push 1
+binary +
+store 5
+var 0
+push 1
+get
+push 0
+var 5
+set
+var 0
+push 2
+get
+release
+push 1
+var 4
+binary -
+store 4
+push 0
+var 4
+binary ==
+unary neg
+push Lincr
+branch
+pop
+pop
+push tt
+ret
@@ -0,0 +1,105 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+push 2
+push 0
+alloc
+push 2
+push tt
+alloc
+peek 3
+push 0
+push Lworker
+set
+peek 3
+push 1
+peek 2
+set
+peek 3
+spawn
+pop
+push 300
+Lready:
+var 2
+push 0
+get
+push 1
+binary ==
+unary neg
+push Lready
+branch
+Lgarbage:
+push 4
+push tt
+alloc
+pop
+push 1
+var 4
+binary -
+store 4
+push 0
+var 4
+binary ==
+unary neg
+push Lgarbage
+branch
+var 2
+push 0
+push 2
+set
+Ldone:
+var 2
+push 0
+get
+push 3
+binary ==
+unary neg
+push Ldone
+branch
+var 2
+push 1
+get
+store 2
+pop
+pop
+ret
+Lworker:
+push 3
+push 7
+alloc
+var 0
+push 1
+get
+push 0
+push 1
+set
+Lcollected:
+var 0
+push 1
+get
+push 0
+get
+push 2
+binary ==
+unary neg
+push Lcollected
+branch
+var 0
+push 1
+get
+push 1
+var 4
+push 2
+get
+set
+var 0
+push 1
+get
+push 0
+push 3
+set
+pop
+push tt
+ret
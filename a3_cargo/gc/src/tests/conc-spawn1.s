@@ -14,6 +14,7 @@ push _L4
 set
 var 2
 spawn
+pop
 push tt
 store 2
 ret
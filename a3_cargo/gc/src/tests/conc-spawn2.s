@@ -24,6 +24,7 @@ var 2
 store 3
 var 3
 spawn
+pop
 push tt
 store 2
 pop
@@ -34,6 +34,7 @@ setframe 3
 swap
 call
 spawn
+pop
 var 7
 store 4
 var 4
@@ -45,6 +46,7 @@ setframe 3
 swap
 call
 spawn
+pop
 push tt
 store 2
 pop
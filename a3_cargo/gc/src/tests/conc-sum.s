@@ -0,0 +1,57 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+push 1
+push Lworker
+alloc
+var 2
+spawn
+var 2
+spawn
+var 2
+spawn
+var 2
+spawn
+var 3
+join
+var 4
+join
+binary +
+var 5
+join
+binary +
+var 6
+join
+binary +
+store 2
+pop
+pop
+pop
+pop
+ret
+Lworker:
+push 50000
+push 0
+Lloop:
+push 7
+var 4
+binary /
+var 5
+binary +
+store 5
+push 1
+var 4
+binary -
+store 4
+push 0
+var 4
+binary ==
+unary neg
+push Lloop
+branch
+var 5
+store 4
+pop
+ret
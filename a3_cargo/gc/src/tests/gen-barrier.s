@@ -0,0 +1,48 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+push 1
+push 0
+alloc
+push 100
+push 0
+Lloop:
+push 20
+push 0
+alloc
+pop
+var 2
+push 0
+push 1
+var 3
+alloc
+set
+push 20
+push 0
+alloc
+pop
+var 2
+push 0
+get
+push 0
+get
+var 4
+binary +
+store 4
+push 1
+var 3
+binary -
+store 3
+push 0
+var 3
+binary ==
+unary neg
+push Lloop
+branch
+var 4
+store 2
+pop
+pop
+ret
@@ -0,0 +1,72 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+push 0
+push 300
+push 0
+Lbuild:
+push 10
+var 2
+alloc
+store 2
+var 2
+push 9
+var 3
+set
+push 1
+var 3
+binary -
+store 3
+push 0
+var 3
+binary ==
+unary neg
+push Lbuild
+branch
+push 300
+store 3
+Lwalk:
+var 2
+push 9
+get
+var 4
+binary +
+store 4
+var 2
+push 0
+get
+store 2
+push 1
+var 3
+binary -
+store 3
+push 0
+var 3
+binary ==
+unary neg
+push Lwalk
+branch
+push 2000
+store 3
+Lchurn:
+push 10
+push 0
+alloc
+pop
+push 1
+var 3
+binary -
+store 3
+push 0
+var 3
+binary ==
+unary neg
+push Lchurn
+branch
+var 4
+store 2
+pop
+pop
+ret
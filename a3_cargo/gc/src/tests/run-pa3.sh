@@ -6,13 +6,13 @@ if ! make -s build; then
 fi
 
 pass=( "tests/applam2.o" "tests/applam3.o" "tests/applam4.o" "tests/applam5.o" "tests/applam6.o" "tests/applam.o" "tests/array2.o" \
-	"tests/array3.o" "tests/array.o" "tests/conc-spawn1.o" "tests/conc-spawn2.o" "tests/conc-spawn3.o" "tests/comment.o" "tests/deadvar.o" \
-	"tests/div.o" "tests/dotwice.o" "tests/fact.o" "tests/fib-memo.o" "tests/fib.o" "tests/funptr2.o" "tests/funptr3.o" "tests/funptr.o" \
+	"tests/array3.o" "tests/array.o" "tests/conc-spawn1.o" "tests/conc-spawn2.o" "tests/conc-spawn3.o" "tests/conc-shared.o" "tests/conc-join.o" "tests/conc-channel.o" "tests/conc-channel-gc.o" "tests/conc-close.o" "tests/conc-race.o" "tests/conc-mutex.o" "tests/conc-cas.o" "tests/conc-alloc.o" "tests/conc-sum.o" "tests/gen-barrier.o" "tests/heap-grow.o" "tests/comment.o" "tests/deadvar.o" \
+	"tests/div.o" "tests/dotwice.o" "tests/fact.o" "tests/fact2.o" "tests/fib-memo.o" "tests/fib.o" "tests/funptr2.o" "tests/funptr3.o" "tests/funptr.o" \
 	"tests/heap.o" "tests/heap2.o" "tests/heap3.o" "tests/let1.o" "tests/let2.o" "tests/lists.o" "tests/match2.o" "tests/match.o" \
 	"tests/minus.o" "tests/multi-arg.o" "tests/mu.o" "tests/neg.o" "tests/pair.o" "tests/pairsum.o" "tests/plus.o" "tests/print.o" \
 	"tests/seq.o" "tests/times.o" )
 
-fail=( "tests/fact2.o" )
+fail=( "tests/conc-deadlock.o" "tests/conc-lock-leak.o" "tests/conc-join-cycle.o" )
 
 echo "Cases that should not have a result:"
 for f in "${fail[@]}"
@@ -0,0 +1,56 @@
+//! Checks the heap after a collection. The heap has to be a run of arrays,
+//! each a Vsize header followed by that many values, and every address the
+//! program can still get to, on a stack, in a channel or in an array those
+//! reach, has to point at one of the headers. Arrays nothing reaches are
+//! only checked for their size: the incremental collector leaves them for
+//! its sweep, and what they point at may be gone already.
+
+use grumpy_isa::Val;
+
+pub fn verify(heap: &[Val], roots: &[&Val]) -> Result<(), String> {
+    //Where every array starts
+    let mut starts = vec![false; heap.len()];
+    let mut addr = 0;
+    while addr < heap.len() {
+        match heap[addr] {
+            Val::Vsize(size) if size >= 0 && addr + (size as usize) < heap.len() => {
+                starts[addr] = true;
+                addr += size as usize + 1;
+            },
+            Val::Vsize(size) => return Err(format!("the array at {} has {} values, past the end of the heap at {}", addr, size, heap.len())),
+            ref other => return Err(format!("heap[{}] is {:?} where an array should start", addr, other)),
+        }
+    }
+
+    let check = |addr: usize, holder: &dyn Fn() -> String| {
+        if addr < heap.len() && starts[addr] {
+            Ok(addr)
+        }else if addr >= heap.len() {
+            Err(format!("{} points at {}, past the end of the heap at {}", holder(), addr, heap.len()))
+        }else {
+            let array = (0..addr).rev().find(|&start| starts[start]).unwrap_or(0);
+            Err(format!("{} points at {}, inside the array at {}", holder(), addr, array))
+        }
+    };
+    let mut reached = vec![false; heap.len()];
+    let mut work = Vec::new();
+    for (index, root) in roots.iter().enumerate() {
+        if let Val::Vaddr(addr) = root {
+            work.push(check(*addr, &|| format!("root {}", index))?);
+        }
+    }
+    while let Some(addr) = work.pop() {
+        if reached[addr] {
+            continue;
+        }
+        reached[addr] = true;
+        if let Val::Vsize(size) = heap[addr] {
+            for (cell, val) in heap.iter().enumerate().skip(addr + 1).take(size as usize) {
+                if let Val::Vaddr(next) = val {
+                    work.push(check(*next, &|| format!("heap[{}] in the array at {}", cell, addr))?);
+                }
+            }
+        }
+    }
+    Ok(())
+}
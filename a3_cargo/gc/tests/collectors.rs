@@ -0,0 +1,107 @@
+use gc::{Algorithm, Work, INCREMENT};
+use grumpy_isa::Val;
+
+use Val::{Vaddr, Vi32, Vsize, Vunit};
+
+//Four arrays: a dead one, one the root holds, another dead one, and one only reachable through the second
+fn heap() -> Vec<Val> {
+    vec![
+        Vsize(2), Vi32(1), Vi32(2),
+        Vsize(2), Vi32(3), Vaddr(10),
+        Vsize(1), Vi32(4),
+        Vsize(1), Vi32(5),
+        Vsize(1), Vi32(6),
+    ]
+}
+
+fn collect(algorithm: Algorithm, heap: &mut Vec<Val>, roots: &mut [Val]) -> Work {
+    let mut collector = algorithm.collector();
+    collector.collect(heap, &mut roots.iter_mut().collect::<Vec<_>>()).unwrap()
+}
+
+//Follows addresses from the root to every value they lead to
+fn reachable(heap: &[Val], root: &Val) -> Vec<Val> {
+    let mut values = Vec::new();
+    if let Vaddr(addr) = root {
+        if let Vsize(size) = heap[*addr] {
+            for value in &heap[addr + 1..=addr + size as usize] {
+                match value {
+                    Vaddr(_) => values.extend(reachable(heap, value)),
+                    other => values.push(other.clone()),
+                }
+            }
+        }
+    }
+    values
+}
+
+#[test]
+fn collectors_keep_what_the_roots_reach() {
+    for algorithm in [Algorithm::Copying, Algorithm::MarkCompact, Algorithm::Generational] {
+        let mut heap = heap();
+        let mut roots = [Vi32(0), Vaddr(3)];
+        collect(algorithm, &mut heap, &mut roots);
+        assert_eq!(reachable(&heap, &roots[1]), vec![Vi32(3), Vi32(6)], "{:?}", algorithm);
+        assert_eq!(roots[0], Vi32(0));
+        assert_eq!(heap.len(), 5, "{:?} kept garbage: {:?}", algorithm, heap);
+    }
+}
+
+#[test]
+fn mark_compact_keeps_allocation_order() {
+    let mut heap = heap();
+    let mut roots = [Vaddr(10), Vaddr(3), Vaddr(10)];
+    collect(Algorithm::MarkCompact, &mut heap, &mut roots);
+    assert_eq!(heap, vec![Vsize(2), Vi32(3), Vaddr(3), Vsize(1), Vi32(6)]);
+    assert_eq!(roots, [Vaddr(3), Vaddr(0), Vaddr(3)]);
+}
+
+//Copying moves everything that survives, mark-compact only what had garbage below it
+#[test]
+fn collectors_count_what_they_copied() {
+    let copying = collect(Algorithm::Copying, &mut heap(), &mut [Vaddr(0), Vaddr(3)]);
+    assert_eq!(copying.copied, 8);
+    let compacting = collect(Algorithm::MarkCompact, &mut heap(), &mut [Vaddr(0), Vaddr(3)]);
+    assert_eq!(compacting, Work { pause: 14, copied: 2 });
+}
+
+#[test]
+fn generational_remembers_old_cells_pointing_into_the_nursery() {
+    let mut collector = Algorithm::Generational.collector();
+    //A minor collection promotes the rooted array
+    let mut heap = vec![Vsize(1), Vi32(0)];
+    let mut roots = [Vaddr(0)];
+    collector.must_collect(&heap, 0, 64);
+    collector.collect(&mut heap, &mut roots.iter_mut().collect::<Vec<_>>()).unwrap();
+    //Then it is pointed past a dead array at one only it holds
+    heap.extend([Vsize(1), Vi32(1), Vsize(1), Vi32(2)]);
+    heap[1] = Vaddr(4);
+    collector.stored(&heap, 1, &Vi32(0));
+    collector.must_collect(&heap, 0, 64);
+    collector.collect(&mut heap, &mut roots.iter_mut().collect::<Vec<_>>()).unwrap();
+    assert_eq!(heap, vec![Vsize(1), Vaddr(2), Vsize(1), Vi32(2)]);
+    assert_eq!(roots, [Vaddr(0)]);
+}
+
+#[test]
+fn incremental_keeps_what_was_reachable_when_the_cycle_started() {
+    let mut collector = Algorithm::Incremental.collector();
+    //The root holds the second array, which holds the one after it
+    let mut heap = heap();
+    let mut roots = [Vaddr(3)];
+    collector.must_collect(&heap, 0, 64);
+    collector.collect(&mut heap, &mut roots.iter_mut().collect::<Vec<_>>()).unwrap();
+    //Meanwhile the program takes the last array out of the heap, keeping it where the cycle does not look again
+    let old = std::mem::replace(&mut heap[5], Vi32(0));
+    collector.stored(&heap, 5, &old);
+    //No step goes much past its increment, the most is finishing the array it is on
+    for _step in 0..10 {
+        assert!(collector.step(&mut heap) <= INCREMENT + 3);
+    }
+    assert_eq!(heap, vec![
+        Vsize(2), Vunit, Vunit,
+        Vsize(2), Vi32(3), Vi32(0),
+        Vsize(3), Vunit, Vunit, Vunit,
+        Vsize(1), Vi32(6),
+    ]);
+}
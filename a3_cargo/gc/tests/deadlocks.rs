@@ -0,0 +1,50 @@
+use gc::{run, Backend, Config, RunError};
+use grumpy_isa::{load_program, Instr};
+use std::fs;
+
+fn program(name: &str) -> Vec<Instr> {
+    let path = format!("{}/src/tests/{}.o", env!("CARGO_MANIFEST_DIR"), name);
+    load_program(&fs::read(&path).unwrap()).unwrap()
+}
+
+fn deadlock(name: &str, backend: Backend) -> Vec<String> {
+    match run(program(name), &Config { backend, fuel: Some(1_000_000), ..Config::default() }) {
+        Err(RunError::Deadlock(threads)) => threads,
+        other => panic!("{} on {:?} should deadlock, got {:?}", name, backend, other),
+    }
+}
+
+#[test]
+fn main_waiting_on_a_channel_no_one_sends_on() {
+    let report = ["thread 0 at pc 5 blocked on recv, waiting for a value on chan:0"];
+    assert_eq!(deadlock("conc-deadlock", Backend::Green), report);
+    assert_eq!(deadlock("conc-deadlock", Backend::Os), report);
+}
+
+//Another thread spins forever, but main can still never go ahead
+#[test]
+fn main_waiting_on_a_mutex_a_halted_thread_holds() {
+    let threads = deadlock("conc-lock-leak", Backend::Green);
+    assert_eq!(threads.len(), 3, "{:?}", threads);
+    assert!(threads[0].starts_with("thread 0 at pc ") && threads[0].ends_with("blocked on acquire, waiting for mutex:0 held by thread 1"), "{:?}", threads);
+    assert_eq!(threads[1], "thread 1 halted");
+    assert!(threads[2].ends_with("can run"), "{:?}", threads);
+    let threads = deadlock("conc-lock-leak", Backend::Os);
+    assert_eq!(threads[1], "thread 1 halted");
+    assert_eq!(threads[2], "thread 2 running");
+}
+
+#[test]
+fn main_joining_a_thread_that_waits_on_main() {
+    for backend in [Backend::Green, Backend::Os] {
+        let threads = deadlock("conc-join-cycle", backend);
+        assert!(threads[0].ends_with("blocked on join, waiting for thread 1 to halt"), "{:?}", threads);
+        assert!(threads[1].ends_with("blocked on acquire, waiting for mutex:0 held by thread 0"), "{:?}", threads);
+    }
+}
+
+#[test]
+fn the_error_has_a_line_per_thread() {
+    let err = RunError::Deadlock(vec!["thread 0 at pc 5 blocked on recv, waiting for a value on chan:0".to_string(), "thread 1 halted".to_string()]);
+    assert_eq!(err.to_string(), "deadlock, main can never go ahead\n  thread 0 at pc 5 blocked on recv, waiting for a value on chan:0\n  thread 1 halted");
+}
@@ -0,0 +1,53 @@
+use gc::{run, run_counted, Algorithm, Backend, Config, Growth, RunError};
+use grumpy_isa::{load_program, Instr, Val};
+use std::fs;
+
+fn program(name: &str) -> Vec<Instr> {
+    let path = format!("{}/src/tests/{}.o", env!("CARGO_MANIFEST_DIR"), name);
+    load_program(&fs::read(&path).unwrap()).unwrap()
+}
+
+fn configs() -> Vec<Config> {
+    let mut configs = Vec::new();
+    for collector in [Algorithm::Copying, Algorithm::MarkCompact, Algorithm::Generational, Algorithm::Incremental] {
+        for backend in [Backend::Green, Backend::Os] {
+            configs.push(Config { collector, backend, ..Config::default() });
+        }
+    }
+    configs
+}
+
+//heap-grow.s keeps about 3300 values live, more than the heap starts with
+#[test]
+fn the_heap_grows_as_far_as_it_may() {
+    for config in configs() {
+        let config = Config { heap_size: 256, growth: Growth { max: 8192, ..Growth::default() }, ..config };
+        let (result, stats) = run_counted(program("heap-grow"), &config);
+        assert_eq!(result, Ok(Val::Vi32(45150)), "{:?} {:?}", config.collector, config.backend);
+        assert!(stats.peak > 3300 && stats.peak <= 8192, "{:?} {:?} peaked at {}", config.collector, config.backend, stats.peak);
+    }
+}
+
+#[test]
+fn alloc_runs_out_of_memory_once_the_heap_may_not_grow() {
+    for config in configs() {
+        let config = Config { growth: Growth { max: 2048, ..Growth::default() }, ..config };
+        match run(program("heap-grow"), &config) {
+            Err(RunError::OutOfMemory { size: 10, max: 2048 }) => (),
+            other => panic!("{:?} {:?} should run out of memory, got {:?}", config.collector, config.backend, other),
+        }
+    }
+    let fixed = Config { growth: Growth { max: 1024, ..Growth::default() }, ..Config::default() };
+    assert!(matches!(run(program("fact2"), &fixed), Err(RunError::OutOfMemory { max: 1024, .. })));
+    assert!(run(program("fact2"), &Config::default()).is_ok());
+}
+
+//An array larger than the largest heap never fits, however much is collected
+#[test]
+fn an_array_larger_than_the_largest_heap() {
+    let program = vec![Instr::Push(Val::Vi32(5000)), Instr::Push(Val::Vunit), Instr::Alloc, Instr::Halt];
+    let config = Config { growth: Growth { max: 4096, ..Growth::default() }, ..Config::default() };
+    let err = run(program, &config).unwrap_err();
+    assert_eq!(err, RunError::OutOfMemory { size: 5000, max: 4096 });
+    assert_eq!(err.to_string(), "out of memory, an array of 5000 values does not fit in the largest heap of 4096 values");
+}
@@ -0,0 +1,52 @@
+use gc::{run, Backend, Config, RunError};
+use grumpy_isa::{Instr, Val};
+use std::thread;
+
+#[test]
+fn the_quantum_has_to_be_at_least_one() {
+    let program = vec![Instr::Push(Val::Vi32(1)), Instr::Halt];
+    let config = Config { quantum: 0, ..Config::default() };
+    match run(program.clone(), &config) {
+        Err(RunError::Failed(msg)) => assert_eq!(msg, "the quantum must be at least 1"),
+        other => panic!("ran with a quantum of 0: {:?}", other),
+    }
+    assert_eq!(run(program, &Config { quantum: 1, ..Config::default() }), Ok(Val::Vi32(1)));
+}
+
+//Main spawns a thread that runs `thread` and joins it, with its halt moved `pad` instructions down
+fn spawn_and_join(pad: u32, thread: &[Instr]) -> Vec<Instr> {
+    let mut program = Vec::new();
+    for _ in 0..pad {
+        program.extend([Instr::Push(Val::Vunit), Instr::Pop]);
+    }
+    let main = 2 * pad + 4;
+    program.extend([Instr::SetFrame(0), Instr::Push(Val::Vloc(main)), Instr::Call, Instr::Halt]);
+    program.extend([Instr::Push(Val::Vi32(1)), Instr::Push(Val::Vloc(main + 6)), Instr::Alloc, Instr::Spawn, Instr::Join, Instr::Ret]);
+    program.extend_from_slice(thread);
+    program
+}
+
+#[test]
+fn runs_at_the_same_time_return_to_their_own_halt() {
+    thread::scope(|scope| {
+        for pad in [0, 20] {
+            scope.spawn(move || {
+                for _run in 0..50 {
+                    assert_eq!(run(spawn_and_join(pad, &[Instr::Push(Val::Vi32(6)), Instr::Ret]), &Config::default()), Ok(Val::Vi32(6)), "pad {}", pad);
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn a_thread_that_panics_ends_an_os_thread_run_with_an_error() {
+    let os = Config { backend: Backend::Os, ..Config::default() };
+    let crash = [Instr::Push(Val::Vunit), Instr::Print, Instr::Ret];
+    for (program, thread) in [(spawn_and_join(0, &crash), 1), (crash.to_vec(), 0)] {
+        match run(program, &os) {
+            Err(RunError::Failed(msg)) => assert_eq!(msg, format!("thread {} panicked: PRINT: Cannot print out values that are not I32", thread)),
+            other => panic!("thread {} panicked and the run gave back {:?}", thread, other),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use gc::scheduler::{Priority, Random, RoundRobin};
+use gc::{run, Config, Policy, Scheduler};
+use grumpy_isa::{load_program, Instr, Val};
+use std::fs;
+
+fn program(name: &str) -> Vec<Instr> {
+    let path = format!("{}/src/tests/{}.o", env!("CARGO_MANIFEST_DIR"), name);
+    load_program(&fs::read(&path).unwrap()).unwrap()
+}
+
+fn picks(scheduler: &mut dyn Scheduler, runnable: &[usize], n: usize) -> Vec<(usize, u32)> {
+    (0..n).map(|_| scheduler.pick(runnable, 3)).collect()
+}
+
+#[test]
+fn round_robin_goes_through_the_threads_in_rounds() {
+    let mut scheduler = RoundRobin::new(5);
+    assert_eq!(picks(&mut scheduler, &[0, 1, 2], 4), vec![(0, 5), (1, 5), (2, 5), (0, 5)]);
+    //A thread that cannot run loses its turn in the round, not its place in the next
+    assert_eq!(picks(&mut scheduler, &[0, 2], 2), vec![(2, 5), (0, 5)]);
+}
+
+#[test]
+fn random_picks_the_same_threads_for_the_same_seed() {
+    let first = picks(&mut Random::new(4, 7), &[0, 1, 2], 50);
+    assert_eq!(first, picks(&mut Random::new(4, 7), &[0, 1, 2], 50));
+    assert_ne!(first, picks(&mut Random::new(4, 8), &[0, 1, 2], 50));
+    for (thread, slice) in first {
+        assert!(thread <= 2 && (1..=4).contains(&slice), "picked thread {} for {}", thread, slice);
+    }
+    //Seed 0 still moves
+    let zero = picks(&mut Random::new(4, 0), &[0, 1, 2], 50);
+    assert!(zero.iter().any(|&pick| pick != zero[0]), "{:?}", zero);
+}
+
+#[test]
+fn priority_runs_the_highest_and_equals_take_turns() {
+    let mut scheduler = Priority::new(2, vec![0, 2, 2]);
+    assert_eq!(picks(&mut scheduler, &[0, 1, 2], 3), vec![(1, 2), (2, 2), (1, 2)]);
+    assert_eq!(picks(&mut scheduler, &[0, 2], 2), vec![(2, 2), (2, 2)]);
+    //Threads past the end of the list have priority 0, and the turn goes on from the last thread that ran
+    assert_eq!(picks(&mut scheduler, &[0, 3], 2), vec![(3, 2), (0, 2)]);
+}
+
+//The same seed has to give the same interleaving, and the locked counter has to come out right under every one
+#[test]
+fn every_policy_repeats_itself_and_keeps_the_mutex() {
+    let configs = [
+        Config { quantum: 1, ..Config::default() },
+        Config { quantum: 50, ..Config::default() },
+        Config { policy: Policy::Random(7), ..Config::default() },
+        Config { policy: Policy::Random(8), quantum: 10, ..Config::default() },
+        Config { policy: Policy::Priority(vec![0, 2, 1]), ..Config::default() },
+    ];
+    for config in &configs {
+        let config = Config { fuel: Some(10_000_000), ..config.clone() };
+        let first = run(program("conc-race"), &config);
+        assert!(first.is_ok(), "{:?}: {:?}", config.policy, first);
+        assert_eq!(first, run(program("conc-race"), &config), "{:?} ran conc-race differently twice", config.policy);
+        assert_eq!(run(program("conc-mutex"), &config), Ok(Val::Vi32(20)), "{:?} lost an update under the mutex", config.policy);
+    }
+}
@@ -0,0 +1,69 @@
+use gc::{run, run_counted, Algorithm, Config, Event, RunError, Stats};
+use grumpy_isa::{load_program, Instr, Val};
+use std::{env, fs, process};
+
+fn program(name: &str) -> Vec<Instr> {
+    let path = format!("{}/src/tests/{}.o", env!("CARGO_MANIFEST_DIR"), name);
+    load_program(&fs::read(&path).unwrap()).unwrap()
+}
+
+#[test]
+fn an_event_is_one_line_of_json() {
+    let event = Event {
+        collection: 1, collector: "copying", kind: "full", trigger: "heap full", thread: 0, wanted: 10, heap_size: 1024,
+        objects_before: 93, slots_before: 1019, objects_after: 3, slots_after: 33, live_after: Some(33),
+        copied: 33, roots: vec![12, 7], channel_roots: 0, pause: 1054, resized_to: 1024,
+    };
+    assert_eq!(event.to_json(), "{\"collection\":1,\"collector\":\"copying\",\"kind\":\"full\",\"trigger\":\"heap full\",\"thread\":0,\
+                                 \"wanted\":10,\"heap_size\":1024,\"objects_before\":93,\"slots_before\":1019,\"objects_after\":3,\
+                                 \"slots_after\":33,\"live_after\":33,\"values_copied\":33,\"bytes_copied\":264,\"roots\":[12,7],\
+                                 \"channel_roots\":0,\"pause\":1054,\"resized_to\":1024}");
+    let unknown = Event { live_after: None, roots: vec![], ..event };
+    assert!(unknown.to_json().contains("\"live_after\":null,") && unknown.to_json().contains("\"roots\":[],"));
+}
+
+#[test]
+fn the_summary_totals_every_collection() {
+    let mut stats = Stats::new(None).unwrap();
+    assert_eq!(stats.summary(), "GC: 0 collections, 0 bytes (0 values) copied, peak heap 0 values, longest pause 0 instructions");
+    let event = Event {
+        collection: 1, collector: "copying", kind: "full", trigger: "heap full", thread: 0, wanted: 1, heap_size: 8,
+        objects_before: 2, slots_before: 4, objects_after: 1, slots_after: 2, live_after: Some(2),
+        copied: 2, roots: vec![1], channel_roots: 0, pause: 5, resized_to: 8,
+    };
+    stats.record(&event).unwrap();
+    stats.record(&Event { collection: 2, copied: 3, ..event }).unwrap();
+    stats.allocated(&[Val::Vsize(2), Val::Vunit, Val::Vunit], 9);
+    stats.allocated(&[Val::Vsize(0)], 4);
+    assert_eq!(stats.summary(), "GC: 2 collections, 40 bytes (5 values) copied, peak heap 3 values, longest pause 9 instructions");
+}
+
+//The file has a line for each collection run_counted() counted, and the values copied add up to its total
+#[test]
+fn run_counted_writes_a_line_per_collection() {
+    for collector in [Algorithm::Copying, Algorithm::MarkCompact, Algorithm::Generational, Algorithm::Incremental] {
+        let path = env::temp_dir().join(format!("gc-stats-{}-{}.jsonl", process::id(), collector.collector().name()));
+        let config = Config { heap_size: 256, collector, stats: Some(path.clone()), ..Config::default() };
+        let (result, stats) = run_counted(program("heap-grow"), &config);
+        let log = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result, Ok(Val::Vi32(45150)), "{:?}", collector);
+        assert!(stats.collections > 1, "{:?}", collector);
+        assert_eq!(log.lines().count() as u64, stats.collections, "{:?}", collector);
+        let copied: u64 = log.lines().map(|line| {
+            let value = &line[line.find("\"values_copied\":").unwrap() + 16..];
+            value[..value.find(',').unwrap()].parse::<u64>().unwrap()
+        }).sum();
+        assert_eq!(copied, stats.copied, "{:?}", collector);
+        assert!(stats.peak > 256 && stats.longest_pause > 0, "{:?}: {}", collector, stats.summary());
+    }
+}
+
+#[test]
+fn a_stats_file_that_cannot_be_written() {
+    let missing = env::temp_dir().join("gc-stats-missing").join("stats.jsonl");
+    match run(program("heap-grow"), &Config { stats: Some(missing), ..Config::default() }) {
+        Err(RunError::Failed(msg)) => assert!(msg.starts_with("GC: cannot write the stats to"), "{}", msg),
+        other => panic!("ran without somewhere to write the stats: {:?}", other),
+    }
+}
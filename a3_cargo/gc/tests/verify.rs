@@ -0,0 +1,100 @@
+use gc::{run, run_counted, verify, Algorithm, Config, RunError};
+use grumpy_isa::{load_program, Instr, Val};
+use std::fs;
+
+use Val::{Vaddr, Vi32, Vsize, Vunit};
+
+//Two arrays, the second pointing at the first
+fn heap() -> Vec<Val> {
+    vec![Vsize(1), Vi32(1), Vsize(2), Vaddr(0), Vunit]
+}
+
+#[test]
+fn every_collector_leaves_a_heap_that_verifies() {
+    for algorithm in [Algorithm::Copying, Algorithm::MarkCompact, Algorithm::Generational, Algorithm::Incremental] {
+        let mut heap = heap();
+        heap.extend([Vsize(1), Vaddr(2)]);
+        let mut roots = [Vaddr(2), Vi32(7), Vaddr(5)];
+        let mut collector = algorithm.collector();
+        collector.must_collect(&heap, 0, 64);
+        collector.collect(&mut heap, &mut roots.iter_mut().collect::<Vec<_>>()).unwrap();
+        assert_eq!(verify(&heap, &roots.iter().collect::<Vec<_>>()), Ok(()), "{:?} left {:?}", algorithm, heap);
+    }
+}
+
+#[test]
+fn addresses_have_to_point_where_an_array_starts() {
+    assert_eq!(verify(&heap(), &[&Vaddr(2)]), Ok(()));
+    assert_eq!(verify(&heap(), &[&Vaddr(3)]), Err("root 0 points at 3, inside the array at 2".to_string()));
+    assert_eq!(verify(&heap(), &[&Vi32(0), &Vaddr(9)]), Err("root 1 points at 9, past the end of the heap at 5".to_string()));
+    let mut inside = heap();
+    inside[3] = Vaddr(1);
+    assert_eq!(verify(&inside, &[&Vaddr(2)]), Err("heap[3] in the array at 2 points at 1, inside the array at 0".to_string()));
+    //Unless nothing reaches them
+    assert_eq!(verify(&inside, &[]), Ok(()));
+}
+
+#[test]
+fn sizes_have_to_add_up_to_the_heap() {
+    let mut short = heap();
+    short.pop();
+    assert_eq!(verify(&short, &[]), Err("the array at 2 has 2 values, past the end of the heap at 4".to_string()));
+    let mut headless = heap();
+    headless[2] = Vi32(2);
+    assert_eq!(verify(&headless, &[]), Err("heap[2] is Vi32(2) where an array should start".to_string()));
+}
+
+#[test]
+fn copying_will_not_collect_a_broken_heap() {
+    let mut collector = Algorithm::Copying.collector();
+    let mut collect = |mut heap: Vec<Val>, mut roots: Vec<Val>| collector.collect(&mut heap, &mut roots.iter_mut().collect::<Vec<_>>());
+    assert_eq!(collect(heap(), vec![Vaddr(3)]), Err("a root points at 3, which is not where an array starts".to_string()));
+    assert_eq!(collect(heap(), vec![Vaddr(9)]), Err("a root points at 9, past the end of the heap at 5".to_string()));
+    let mut inside = heap();
+    inside[3] = Vaddr(1);
+    assert_eq!(collect(inside, vec![Vaddr(2)]), Err("heap[1] points at 1, which is not where an array starts".to_string()));
+    let mut short = heap();
+    short.pop();
+    assert_eq!(collect(short, vec![Vaddr(2)]), Err("a root points at 2, an array of 2 values past the end of the heap at 4".to_string()));
+}
+
+fn program(name: &str) -> Vec<Instr> {
+    let path = format!("{}/src/tests/{}.o", env!("CARGO_MANIFEST_DIR"), name);
+    load_program(&fs::read(&path).unwrap()).unwrap()
+}
+
+//Stress collects before every alloc, so each of heap3.s's allocs is a collection that has to verify
+#[test]
+fn stress_collects_at_every_alloc_and_still_gets_the_result() {
+    let expected = run(program("heap3"), &Config::default()).unwrap();
+    let allocs = program("heap3").iter().filter(|instr| **instr == Instr::Alloc).count() as u64;
+    for collector in [Algorithm::Copying, Algorithm::MarkCompact, Algorithm::Generational, Algorithm::Incremental] {
+        let (result, stats) = run_counted(program("heap3"), &Config { collector, stress: true, ..Config::default() });
+        assert_eq!(result, Ok(expected.clone()), "{:?}", collector);
+        assert!(stats.collections >= allocs, "{:?} collected {} times for {} allocs", collector, stats.collections, allocs);
+    }
+}
+
+//A root pointing inside an array, which only a collection ever looks at
+#[test]
+fn a_broken_heap_stops_the_run() {
+    let program = vec![
+        Instr::Push(Val::Vi32(2)), Instr::Push(Val::Vunit), Instr::Alloc,
+        Instr::Push(Val::Vaddr(1)),
+        Instr::Push(Val::Vi32(1)), Instr::Push(Val::Vunit), Instr::Alloc,
+        Instr::Push(Val::Vi32(0)), Instr::Halt,
+    ];
+    assert_eq!(run(program.clone(), &Config::default()), Ok(Val::Vi32(0)));
+    let errors: Vec<String> = [Algorithm::Copying, Algorithm::MarkCompact, Algorithm::Generational, Algorithm::Incremental].iter()
+        .map(|&collector| match run(program.clone(), &Config { collector, stress: true, ..Config::default() }) {
+            Err(RunError::Failed(msg)) => msg,
+            other => panic!("{:?} collected a broken heap and gave back {:?}", collector, other),
+        })
+        .collect();
+    assert_eq!(errors, [
+        "GC: the copying collector cannot collect a broken heap: a root points at 1, which is not where an array starts",
+        "GC: the heap is broken after the mark-compact collector ran: root 1 points at 1, inside the array at 0",
+        "GC: the heap is broken after the generational collector ran: root 1 points at 1, inside the array at 0",
+        "GC: the heap is broken after the incremental collector ran: root 1 points at 1, inside the array at 0",
+    ]);
+}
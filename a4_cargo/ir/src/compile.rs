@@ -48,12 +48,12 @@ pub fn compile(e: &Exp, prho: &mut HashMap<String, u32>, counter: &mut u32) -> V
             let mut is_rhs = compile(&b.rhs, &mut rho, &mut cnt);
             let mut is_op = 
                 match b.op.clone() {
-                    BPlus => vec![IPlus],
-                    BTimes => vec![ITimes],
-                    BMinus => vec![IMinus],
-                    BDivide => vec![IDivide],
-                    BLt => vec![ILt],
-                    BEq => vec![IEq]
+                    Add => vec![IPlus],
+                    Mul => vec![ITimes],
+                    Sub => vec![IMinus],
+                    Div => vec![IDivide],
+                    Lt => vec![ILt],
+                    Eq => vec![IEq]
                 };
             let mut is = vec![];
             is.append(&mut is_lhs);
@@ -65,7 +65,7 @@ pub fn compile(e: &Exp, prho: &mut HashMap<String, u32>, counter: &mut u32) -> V
             let mut is_exp = compile(&u.exp, &mut rho, &mut cnt);
             let mut is_op = 
                 match u.op.clone() {
-                    UNeg => vec![INeg]
+                    Unop::Neg => vec![INeg]
                 };
             let mut is = vec![];
             is.append(&mut is_exp);
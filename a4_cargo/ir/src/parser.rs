@@ -129,42 +129,42 @@ fn parse_binop(l: &mut LexerState) -> Result<Exp, String> {
             let e1 = parse_exp(l)?;
             let e2 = parse_exp(l)?;
             l.eat(RIGHT_PAREN);
-            Ok(EBinop(Box::new(Binexp{op: BPlus, lhs: e2, rhs: e1})))
+            Ok(EBinop(Box::new(Binexp{op: Add, lhs: e2, rhs: e1})))
         },
         MINUS => {
             l.eat(MINUS);
             let e1 = parse_exp(l)?;
             let e2 = parse_exp(l)?;
             l.eat(RIGHT_PAREN);
-            Ok(EBinop(Box::new(Binexp{op: BMinus, lhs: e2, rhs: e1})))
+            Ok(EBinop(Box::new(Binexp{op: Sub, lhs: e2, rhs: e1})))
         },
         TIMES => {
             l.eat(TIMES);
             let e1 = parse_exp(l)?;
             let e2 = parse_exp(l)?;
             l.eat(RIGHT_PAREN);
-            Ok(EBinop(Box::new(Binexp{op: BTimes, lhs: e2, rhs: e1})))
+            Ok(EBinop(Box::new(Binexp{op: Mul, lhs: e2, rhs: e1})))
         },
         DIVISION => {
             l.eat(DIVISION);
             let e1 = parse_exp(l)?;
             let e2 = parse_exp(l)?;
             l.eat(RIGHT_PAREN);
-            Ok(EBinop(Box::new(Binexp{op: BDivide, lhs: e2, rhs: e1})))
+            Ok(EBinop(Box::new(Binexp{op: Div, lhs: e2, rhs: e1})))
         },
         LT => {
             l.eat(LT);
             let e1 = parse_exp(l)?;
             let e2 = parse_exp(l)?;
             l.eat(RIGHT_PAREN);
-            Ok(EBinop(Box::new(Binexp{op: BLt, lhs: e2, rhs: e1})))
+            Ok(EBinop(Box::new(Binexp{op: Lt, lhs: e2, rhs: e1})))
         },
         EQ => {
             l.eat(EQ);
             let e1 = parse_exp(l)?;
             let e2 = parse_exp(l)?;
             l.eat(RIGHT_PAREN);
-            Ok(EBinop(Box::new(Binexp{op: BEq, lhs: e2, rhs: e1})))
+            Ok(EBinop(Box::new(Binexp{op: Eq, lhs: e2, rhs: e1})))
         },
         tok => parse_err!(l, format!("bionp: unexpected token {:?}", tok))
     }
@@ -176,7 +176,7 @@ fn parse_unop(l: &mut LexerState) -> Result<Exp, String> {
             l.eat(NEG);
             let e1 = parse_exp(l)?;
             l.eat(RIGHT_PAREN);
-            Ok(EUnop(Box::new(Unexp{op: UNeg, exp: e1})))
+            Ok(EUnop(Box::new(Unexp{op: Neg, exp: e1})))
         },
         tok => parse_err!(l, format!("unop: unexpected token {:?}", tok))
     }
@@ -1,15 +1,5 @@
 use std::string::{ToString};
-
-
-#[derive(Debug,Clone)]
-pub enum Binop {
-    BPlus,
-    BTimes,
-    BMinus,
-    BDivide,
-    BLt,
-    BEq,
-}
+pub use grumpy_isa::{Binop, Unop};
 
 
 #[derive(Debug, Clone)]
@@ -28,11 +18,6 @@ pub enum Value {
 }
 
 
-#[derive(Debug, Clone)]
-pub enum Unop {
-    UNeg,
-}
-
 #[derive(Debug, Clone)]
 pub struct Unexp {
     pub op: Unop,
@@ -1,9 +1,7 @@
 use std::collections::HashMap;
 use crate::types::*;
-use crate::types::Binop::*;
 use crate::types::Exp::*;
 use crate::types::Instr::*;
-use crate::types::Unop::*;
 use crate::types::Val::*;
 
 static mut labelGen: i32 = 0;
@@ -52,7 +50,7 @@ pub fn compile_fun(fun: &Fun, prho: &mut HashMap<String, u32>) -> Vec<Instr> {
 
     }
 
-    let mut funlabel = "L".to_string() + &fun.name + ":";
+    let mut funlabel = "L".to_string() + &fun.name;
     let mut is_body = compile(&fun.body, &mut rho);
     ret.push(Label(funlabel));
     ret.append(&mut is_body);
@@ -80,9 +78,15 @@ pub fn compile(e: &Exp, prho: &mut HashMap<String, u32>) -> Vec<Instr> {
             incr_loc(&mut rho);
             ret.push(Call);
             ret.push(Halt);
-            ret.push(Label("Lmain:".to_string()));
+            ret.push(Label("Lmain".to_string()));
             let mut mexp = compile(&prog.mainexp, &mut rho);
             ret.append(&mut mexp);
+            //main has to return before the functions, or it runs on into the first one. Every call is followed by a
+            //ret and branches only go to labels, so main can only reach its end if no ret follows its last label
+            let falls_off = ret.iter().rev().take_while(|i| !matches!(i, Label(_))).all(|i| *i != Ret);
+            if !prog.funlist.is_empty() && falls_off {
+                ret.push(Ret);
+            }
             let mut fnlist = compile_funlist(&prog.funlist, &mut rho);
             ret.append(&mut fnlist);
             ret.push(Ret);
@@ -103,15 +107,7 @@ pub fn compile(e: &Exp, prho: &mut HashMap<String, u32>) -> Vec<Instr> {
             decr_loc(&mut rho);
             let mut is_lhs = compile(&b.lhs, &mut rho);
             let mut is_rhs = compile(&b.rhs, &mut rho);
-            let mut is_op = 
-                match b.op.clone() {
-                    Plus => vec![Binary(Plus)],
-                    Times => vec![Binary(Times)],
-                    Minus => vec![Binary(Minus)],
-                    Divide => vec![Binary(Divide)],
-                    Lt => vec![Binary(Lt)],
-                    Eq => vec![Binary(Eq)]
-                };
+            let mut is_op = vec![Binary(b.op.clone())];
             let mut is = vec![];
             is.append(&mut is_lhs);
             is.append(&mut is_rhs);
@@ -121,10 +117,7 @@ pub fn compile(e: &Exp, prho: &mut HashMap<String, u32>) -> Vec<Instr> {
 ///Unop
         EUnop(u) => {
             let mut is_exp = compile(&u.exp, &mut rho);
-            let mut is_op = 
-                match u.op.clone() {
-                    UNeg => vec![Unary(Neg)]
-                };
+            let mut is_op = vec![Unary(u.op.clone())];
             let mut is = vec![];
             is.append(&mut is_exp);
             is.append(&mut is_op);
@@ -247,12 +240,82 @@ pub fn compile(e: &Exp, prho: &mut HashMap<String, u32>) -> Vec<Instr> {
             is.push(Push(Vbool(true)));
             is.push(Push(Vlabel(_Lend.clone())));
             is.push(Branch);
-            is.push(Label(_Lthen.clone() + ":"));
+            is.push(Label(_Lthen.clone()));
             is.append(&mut is_exp1);
             is.push(Push(Vbool(true)));
             is.push(Push(Vlabel(_Lend.clone())));
             is.push(Branch);
-            is.push(Label(_Lend.clone() + ":"));
+            is.push(Label(_Lend.clone()));
+            is
+        },
+//Spawn
+        ESpawn(s) => {
+            //The VM runs a closure, so wrap the function in one that holds nothing else
+            incr_loc(rho);
+            vec![Push(Vi32(1)), Push(Vlabel("L".to_string() + &s.id)), Alloc, Spawn]
+        },
+//Join
+        EJoin(j) => {
+            let mut is = compile(&j.exp, rho);
+            is.push(Join);
+            is
+        },
+//Channel, whose type only matters to the type checker
+        EChannel(_) => {
+            incr_loc(rho);
+            vec![Chan]
+        },
+//Send, which gives back unit like set
+        ESend(s) => {
+            decr_loc(rho);
+            let mut is = compile(&s.echan, rho);
+            is.append(&mut compile(&s.e1, rho));
+            is.push(Send);
+            is.push(Push(Vunit));
+            is
+        },
+//Recv
+        ERecv(r) => {
+            let mut is = compile(&r.echan, rho);
+            is.push(Recv);
+            is
+        },
+//Close
+        EClose(c) => {
+            let mut is = compile(&c.echan, rho);
+            is.push(Close);
+            is.push(Push(Vunit));
+            is
+        },
+//Mutex
+        EMutex => {
+            incr_loc(rho);
+            vec![Mutex]
+        },
+//Acquire, which gives back unit
+        EAcquire(a) => {
+            let mut is = compile(&a.emutex, rho);
+            is.push(Acquire);
+            is.push(Push(Vunit));
+            is
+        },
+//Release
+        ERelease(r) => {
+            let mut is = compile(&r.emutex, rho);
+            is.push(Release);
+            is.push(Push(Vunit));
+            is
+        },
+//Cas, which gives back whether it set the element
+        ECas(c) => {
+            decr_loc(rho);
+            decr_loc(rho);
+            decr_loc(rho);
+            let mut is = compile(&c.earr, rho);
+            is.append(&mut compile(&c.eidx, rho));
+            is.append(&mut compile(&c.eold, rho));
+            is.append(&mut compile(&c.enew, rho));
+            is.push(Cas);
             is
         },
     }// match e
@@ -23,6 +23,15 @@ pub enum Token {
     RIGHT_PAREN,
     PRINT,
     SPAWN,
+    JOIN,
+    CHANNEL,
+    SEND,
+    RECV,
+    CLOSE,
+    MUTEX,
+    ACQUIRE,
+    RELEASE,
+    CAS,
     NEG,
     PLUS,
     TIMES,
@@ -64,6 +73,11 @@ macro_rules! lex_upd {
 }
 
 
+//The thread keywords only count as a whole word, so case, sender or closed are still names
+fn keyword(s: &str, word: &str) -> bool {
+    s.starts_with(word) && !s[word.len()..].starts_with(|c: char| c.is_alphanumeric() || c == '_')
+}
+
 fn lex<'a>(l: &mut LexerState<'a>) -> Result<Token, String> {
     let s = l.rest;
     let int_regex = Regex::new(r"^\A[[:digit:]]+").unwrap();
@@ -133,7 +147,16 @@ fn lex<'a>(l: &mut LexerState<'a>) -> Result<Token, String> {
    else if s.starts_with("fun") { lex_upd!(l, 3, Token::FUNDECL)}
    else if s.starts_with("call") { lex_upd!(l, 4, Token::CALL)}
    else if s.starts_with("print") { lex_upd!(l, 5, Token::PRINT)}
-   else if s.starts_with("spawn") { lex_upd!(l, 5, Token::SPAWN)}
+   else if keyword(s, "spawn") { lex_upd!(l, 5, Token::SPAWN)}
+   else if keyword(s, "join") { lex_upd!(l, 4, Token::JOIN)}
+   else if keyword(s, "channel") { lex_upd!(l, 7, Token::CHANNEL)}
+   else if keyword(s, "send") { lex_upd!(l, 4, Token::SEND)}
+   else if keyword(s, "recv") { lex_upd!(l, 4, Token::RECV)}
+   else if keyword(s, "close") { lex_upd!(l, 5, Token::CLOSE)}
+   else if keyword(s, "mutex") { lex_upd!(l, 5, Token::MUTEX)}
+   else if keyword(s, "acquire") { lex_upd!(l, 7, Token::ACQUIRE)}
+   else if keyword(s, "release") { lex_upd!(l, 7, Token::RELEASE)}
+   else if keyword(s, "cas") { lex_upd!(l, 3, Token::CAS)}
    else if s.starts_with("i32") { lex_upd!(l, 3, Token::INT_TYPE)}
    else if s.starts_with("bool") { lex_upd!(l, 4, Token::BOOL_TYPE)}
    else if s.starts_with("unit") { lex_upd!(l, 4, Token::UNIT_TYPE)}
@@ -250,3 +273,27 @@ impl<'a> LexerState<'a> {
         else { None }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(source: &str) -> Vec<Token> {
+        let mut l = LexerState::new(source);
+        let mut tokens = Vec::new();
+        while !l.rest.is_empty() {
+            tokens.push(l.next().unwrap());
+        }
+        tokens
+    }
+
+    #[test]
+    fn names_that_start_with_a_thread_keyword_are_names() {
+        let names = ["case", "sender", "closed", "joined", "castle", "spawner", "channels", "recvd", "mutexes", "acquired", "released"];
+        for name in names {
+            assert_eq!(tokens(name), vec![Token::ID(name.to_string())]);
+        }
+        assert_eq!(tokens("(cas a)"), vec![Token::LEFT_PAREN, Token::CAS, Token::ID("a".to_string()), Token::RIGHT_PAREN]);
+        assert_eq!(tokens("(send case)"), vec![Token::LEFT_PAREN, Token::SEND, Token::ID("case".to_string()), Token::RIGHT_PAREN]);
+    }
+}
@@ -0,0 +1,25 @@
+use std::collections::HashMap;
+use crate::types::Instr;
+
+#[allow(dead_code)]
+pub mod lexer;
+
+#[allow(dead_code)]
+pub mod parser;
+use parser::{parse};
+
+#[allow(dead_code)]
+pub mod types;
+
+#[allow(dead_code)]
+pub mod compile;
+use compile::{compile};
+
+/// Parses and compiles a whole Grumpy program, returning its instructions
+/// with labels still unresolved.
+pub fn compile_source(buf: &str) -> Result<Vec<Instr>, String> {
+    let e = parse(buf)?;
+    let mut rho: HashMap<String, u32> = HashMap::new();
+    rho.insert("$$".to_string(), 0);
+    Ok(compile(&e, &mut rho))
+}
@@ -231,6 +231,26 @@ fn parse_exp(l: &mut LexerState) -> Result<Exp, String> {
                     Ok(ECall(Box::new(Callexp{funptr: EFunptr(Box::new(Funptrexp{id: ptr})), args: explist})))
                     
                 },
+                SPAWN => {
+                    let ret = parse_spawn(l);
+                    l.eat(RIGHT_PAREN);
+                    ret
+                },
+                JOIN => {
+                    let ret = parse_join(l);
+                    l.eat(RIGHT_PAREN);
+                    ret
+                },
+                CHANNEL | SEND | RECV | CLOSE => {
+                    let ret = parse_channel(l);
+                    l.eat(RIGHT_PAREN);
+                    ret
+                },
+                MUTEX | ACQUIRE | RELEASE | CAS => {
+                    let ret = parse_mutex(l);
+                    l.eat(RIGHT_PAREN);
+                    ret
+                },
                 FUNPTR => {
                     let ret = parse_funptr(l);
                     l.eat(RIGHT_PAREN);
@@ -281,25 +301,25 @@ fn parse_binop(l: &mut LexerState) -> Result<Exp, String> {
             l.eat(PLUS);
             let e1 = parse_exp(l)?;
             let e2 = parse_exp(l)?;
-            Ok(EBinop(Box::new(Binexp{op: Plus, lhs: e2, rhs: e1})))
+            Ok(EBinop(Box::new(Binexp{op: Add, lhs: e2, rhs: e1})))
         },
         MINUS => {
             l.eat(MINUS);
             let e1 = parse_exp(l)?;
             let e2 = parse_exp(l)?;
-            Ok(EBinop(Box::new(Binexp{op: Minus, lhs: e2, rhs: e1})))
+            Ok(EBinop(Box::new(Binexp{op: Sub, lhs: e2, rhs: e1})))
         },
         TIMES => {
             l.eat(TIMES);
             let e1 = parse_exp(l)?;
             let e2 = parse_exp(l)?;
-            Ok(EBinop(Box::new(Binexp{op: Times, lhs: e2, rhs: e1})))
+            Ok(EBinop(Box::new(Binexp{op: Mul, lhs: e2, rhs: e1})))
         },
         DIVISION => {
             l.eat(DIVISION);
             let e1 = parse_exp(l)?;
             let e2 = parse_exp(l)?;
-            Ok(EBinop(Box::new(Binexp{op: Divide, lhs: e2, rhs: e1})))
+            Ok(EBinop(Box::new(Binexp{op: Div, lhs: e2, rhs: e1})))
         },
         LT => {
             l.eat(LT);
@@ -405,6 +425,83 @@ fn parse_cond(l: &mut LexerState) -> Result<Exp, String> {
     }
 }
 
+fn parse_spawn(l: &mut LexerState) -> Result<Exp, String> {
+    match l.peek().expect("spawn: expected a token") {
+        SPAWN => {
+            l.eat(SPAWN);
+            let name = parse_id(l)?;
+            Ok(ESpawn(Box::new(Spawnexp{id: name})))
+        },
+        tok => parse_err!(l, format!("spawn: unexpected token {:?}", tok))
+    }
+}
+
+fn parse_join(l: &mut LexerState) -> Result<Exp, String> {
+    match l.peek().expect("join: expected a token") {
+        JOIN => {
+            l.eat(JOIN);
+            let thread = parse_exp(l)?;
+            Ok(EJoin(Box::new(Joinexp{exp: thread})))
+        },
+        tok => parse_err!(l, format!("join: unexpected token {:?}", tok))
+    }
+}
+
+fn parse_channel(l: &mut LexerState) -> Result<Exp, String> {
+    match l.peek().expect("channel: expected a token") {
+        CHANNEL => {
+            l.eat(CHANNEL);
+            let ty = parse_type(l)?;
+            Ok(EChannel(Box::new(Channelexp{ty})))
+        },
+        SEND => {
+            l.eat(SEND);
+            let chan = parse_exp(l)?;
+            let val = parse_exp(l)?;
+            Ok(ESend(Box::new(Sendexp{echan: chan, e1: val})))
+        },
+        RECV => {
+            l.eat(RECV);
+            let chan = parse_exp(l)?;
+            Ok(ERecv(Box::new(Chanexp{echan: chan})))
+        },
+        CLOSE => {
+            l.eat(CLOSE);
+            let chan = parse_exp(l)?;
+            Ok(EClose(Box::new(Chanexp{echan: chan})))
+        },
+        tok => parse_err!(l, format!("channel: unexpected token {:?}", tok))
+    }
+}
+
+fn parse_mutex(l: &mut LexerState) -> Result<Exp, String> {
+    match l.peek().expect("mutex: expected a token") {
+        MUTEX => {
+            l.eat(MUTEX);
+            Ok(EMutex)
+        },
+        ACQUIRE => {
+            l.eat(ACQUIRE);
+            let mutex = parse_exp(l)?;
+            Ok(EAcquire(Box::new(Mutexexp{emutex: mutex})))
+        },
+        RELEASE => {
+            l.eat(RELEASE);
+            let mutex = parse_exp(l)?;
+            Ok(ERelease(Box::new(Mutexexp{emutex: mutex})))
+        },
+        CAS => {
+            l.eat(CAS);
+            let arr = parse_exp(l)?;
+            let idx = parse_exp(l)?;
+            let old = parse_exp(l)?;
+            let new = parse_exp(l)?;
+            Ok(ECas(Box::new(Casexp{earr: arr, eidx: idx, eold: old, enew: new})))
+        },
+        tok => parse_err!(l, format!("mutex: unexpected token {:?}", tok))
+    }
+}
+
 fn parse_call(l: &mut LexerState) -> Result<Exp, String> {
     match l.peek().expect("call: expected a token") {
         CALL => {
@@ -1,33 +1,6 @@
 use std::string::{ToString};
 use std::collections::VecDeque;
-
-
-
-type Address = usize;
-
-#[derive(Debug,Clone)]
-pub enum Binop {
-    Plus,
-    Times,
-    Minus,
-    Divide,
-    Lt,
-    Eq,
-}
-
-impl ToString for Binop {
-    fn to_string(&self) -> String {
-        match self {
-            Binop::Times => "*".to_string(),
-            Binop::Plus => "+".to_string(),
-            Binop::Minus => "-".to_string(),
-            Binop::Divide => "/".to_string(),
-            Binop::Lt => "<".to_string(),
-            Binop::Eq => "==".to_string()
-        }
-    }
-}
-
+pub use grumpy_isa::{Binop, Instr, Program, Unop, Val};
 
 #[derive(Debug, Clone)]
 pub struct Binexp {
@@ -44,50 +17,6 @@ pub enum Value {
     Unit,
 }
 
-
-#[derive(Debug, Clone)]
-pub enum Val {
-    Vunit,
-    Vi32(i32),
-    Vbool(bool),
-    Vloc(u32),
-    Vundef,
-    Vsize(i32),
-    Vaddr(Address),
-    Vlabel(String)
-}
-
-use crate::types::Val::*;
-
-impl ToString for Val {
-    fn to_string(&self) -> String {
-        match self {
-            Vunit => "tt".to_string(),
-            Vi32(i) => i.to_string(),
-            Vbool(b) => b.to_string(),
-            Vloc(u) => u.to_string(),
-            Vundef => "undef".to_string(),
-            Vsize(i) => i.to_string(),
-            Vaddr(a) => a.to_string(),
-            Vlabel(l) => l.to_string()
-        }
-    }
-}
-
-
-#[derive(Debug, Clone)]
-pub enum Unop {
-    Neg,
-}
-
-impl ToString for Unop {
-    fn to_string(&self) -> String {
-        match self {
-            Neg => "neg".to_string()
-        }
-    }
-}
-
 #[derive(Debug, Clone)]
 pub struct Unexp {
     pub op: Unop,
@@ -177,6 +106,45 @@ pub struct Callexp {
 }
 
 
+#[derive(Debug, Clone)]
+pub struct Spawnexp {
+    pub id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Joinexp {
+    pub exp: Exp,
+}
+
+#[derive(Debug, Clone)]
+pub struct Channelexp {
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sendexp {
+    pub echan: Exp,
+    pub e1: Exp,
+}
+
+#[derive(Debug, Clone)]
+pub struct Chanexp {
+    pub echan: Exp,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mutexexp {
+    pub emutex: Exp,
+}
+
+#[derive(Debug, Clone)]
+pub struct Casexp {
+    pub earr: Exp,
+    pub eidx: Exp,
+    pub eold: Exp,
+    pub enew: Exp,
+}
+
 #[derive(Debug, Clone)]
 pub enum Exp {
     EI32(i32),
@@ -193,31 +161,17 @@ pub enum Exp {
     EGet(Box<Getexp>),
     ECall(Box<Callexp>),
     EFunptr(Box<Funptrexp>),
-    EAlloc(Box<Allocexp>)
+    EAlloc(Box<Allocexp>),
+    ESpawn(Box<Spawnexp>),
+    EJoin(Box<Joinexp>),
+    EChannel(Box<Channelexp>),
+    ESend(Box<Sendexp>),
+    ERecv(Box<Chanexp>),
+    EClose(Box<Chanexp>),
+    EMutex,
+    EAcquire(Box<Mutexexp>),
+    ERelease(Box<Mutexexp>),
+    ECas(Box<Casexp>)
 }
 
 use crate::types::Exp::*;
-
-
-#[derive(Debug, Clone)]
-pub enum Instr {
-    Label(String),
-    Push(Val),
-    Pop,
-    Peek(u32),
-    Unary(Unop),
-    Binary(Binop),
-    Swap,
-    Alloc,
-    Set,
-    Get,
-    Var(u32),
-    Store(u32),
-    SetFrame(u32),
-    Call,
-    Ret,
-    Branch,
-    Halt
-}
-
-use crate::types::Instr::*;
@@ -0,0 +1,27 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+push undef
+push 1
+push 0
+alloc
+store 2
+var 2
+push 0
+push 0
+push 5
+cas
+pop
+var 2
+push 0
+push 0
+push 6
+cas
+pop
+var 2
+push 0
+get
+store 2
+ret
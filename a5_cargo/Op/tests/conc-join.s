@@ -0,0 +1,16 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+push 1
+push Lworker
+alloc
+spawn
+join
+ret
+Lworker:
+push 7
+push 6
+binary *
+ret
@@ -0,0 +1,17 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+push undef
+mutex
+store 2
+var 2
+acquire
+push tt
+pop
+var 2
+release
+push tt
+store 2
+ret
@@ -0,0 +1,12 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+push undef
+chan
+store 2
+var 2
+recv
+store 2
+ret
@@ -0,0 +1,14 @@
+setframe 0
+push Lmain
+call
+halt
+Lmain:
+push undef
+chan
+store 2
+var 2
+push 3
+send
+push tt
+store 2
+ret
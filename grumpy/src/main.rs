@@ -0,0 +1,189 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use grumpy_isa::{load_program, Instr, Program, Val};
+use assemble::Object;
+
+mod options;
+use options::{parse_args, Collector, Command, Format, Machine, Options, Sched, Threads};
+
+fn fail(msg: &str) -> ! {
+    exit_with(1, msg)
+}
+
+fn exit_with(status: i32, msg: &str) -> ! {
+    eprintln!("grumpy: {}", msg);
+    process::exit(status);
+}
+
+fn read_input(input: &str) -> io::Result<Vec<u8>> {
+    if input == "-" {
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes)?;
+        Ok(bytes)
+    } else {
+        fs::read(input)
+    }
+}
+
+fn read_text(input: &str) -> io::Result<String> {
+    String::from_utf8(read_input(input)?).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn write_output(output: &str, bytes: &[u8]) -> io::Result<()> {
+    if output == "-" {
+        let mut handle = io::stdout().lock();
+        handle.write_all(bytes)?;
+        handle.flush()
+    } else {
+        fs::write(output, bytes)
+    }
+}
+
+//asm writes foo.o next to foo.s like the assembler does, everything else goes to stdout
+fn default_output(options: &Options) -> String {
+    let stem = options.input.strip_suffix(".s").or_else(|| options.input.strip_suffix(".json"));
+    match (options.command, stem) {
+        (Command::Asm, Some(stem)) if options.format.is_none() || options.format == Some(Format::Bin) => format!("{}.o", stem),
+        _ => "-".to_string(),
+    }
+}
+
+fn compile(source: &str) -> Vec<Instr> {
+    op::compile_source(source).unwrap_or_else(|err| fail(&format!("compile: {}", err)))
+}
+
+fn to_text(program: &[Instr]) -> String {
+    program.iter().map(|instr| format!("{}\n", instr)).collect()
+}
+
+fn to_json(mut program: Program, options: &Options) -> Vec<u8> {
+    program.metadata.insert("producer".to_string(), "grumpy".to_string());
+    program.metadata.insert("source".to_string(), options.input.clone());
+    program.to_json().into_bytes()
+}
+
+fn assemble(source: &str) -> Object {
+    assemble::assemble_str(source).unwrap_or_else(|err| fail(&format!("asm: {}", err)))
+}
+
+//Either an executable .o or a JSON program
+fn load(bytes: &[u8]) -> Vec<Instr> {
+    load_program(bytes).unwrap_or_else(|err| fail(&format!("load: {}", err)))
+}
+
+//Without a seed the random scheduler takes one from the clock and says which, so a run can be repeated
+fn policy(options: &Options) -> gc::Policy {
+    match options.sched {
+        Sched::RoundRobin => gc::Policy::RoundRobin,
+        Sched::Random => {
+            let seed = options.seed.unwrap_or_else(|| {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                let seed = now.as_secs() ^ u64::from(now.subsec_nanos());
+                eprintln!("grumpy: scheduling with --seed {}", seed);
+                seed
+            });
+            gc::Policy::Random(seed)
+        },
+        Sched::Priority => gc::Policy::Priority(options.priorities.clone()),
+    }
+}
+
+fn run(program: Vec<Instr>, options: &Options) -> Val {
+    let result = match options.machine {
+        Machine::Gc => {
+            let mut config = gc::Config { fuel: options.fuel, trace: options.trace, ..gc::Config::default() };
+            config.heap_size = options.heap_size.unwrap_or(config.heap_size);
+            config.growth.max = options.heap_max.unwrap_or(config.growth.max);
+            config.growth.factor = options.heap_growth.unwrap_or(config.growth.factor);
+            config.growth.live_ratio = options.heap_live.unwrap_or(config.growth.live_ratio);
+            config.stress = options.gc_stress;
+            config.verify = options.gc_verify;
+            config.stats = options.gc_stats.as_ref().map(PathBuf::from);
+            config.quantum = options.quantum.unwrap_or(config.quantum);
+            config.policy = policy(options);
+            config.collector = match options.collector {
+                Collector::Copying => gc::Algorithm::Copying,
+                Collector::MarkCompact => gc::Algorithm::MarkCompact,
+                Collector::Generational => gc::Algorithm::Generational,
+                Collector::Incremental => gc::Algorithm::Incremental,
+            };
+            if options.threads == Threads::Os {
+                config.backend = gc::Backend::Os;
+            }
+            //The stats summary goes last, after the error of a run that failed
+            let (result, stats) = gc::run_counted(program, &config);
+            let status = match &result {
+                Err(gc::RunError::Deadlock(_)) => gc::DEADLOCK_EXIT,
+                _ => 1,
+            };
+            match result {
+                Ok(val) => {
+                    eprintln!("{}", stats.summary());
+                    Ok(val)
+                },
+                Err(err) => {
+                    eprintln!("grumpy: run: {}", err);
+                    eprintln!("{}", stats.summary());
+                    process::exit(status);
+                },
+            }
+        },
+        Machine::Vm => {
+            let mut config = vm::Config { fuel: options.fuel, trace: options.trace, ..vm::Config::default() };
+            config.heap_size = options.heap_size.unwrap_or(config.heap_size);
+            vm::run(program, &config)
+        },
+    };
+    result.unwrap_or_else(|err| fail(&format!("run: {}", err)))
+}
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    let options = parse_args(&args);
+    let output = options.output.clone().unwrap_or_else(|| default_output(&options));
+
+    let bytes = match options.command {
+        Command::Compile => {
+            let program = compile(&read_text(&options.input)?);
+            match options.format {
+                Some(Format::Bin) => assemble(&to_text(&program)).to_bytes(),
+                Some(Format::Json) => to_json(Program::from_instrs(&program), &options),
+                _ => to_text(&program).into_bytes(),
+            }
+        },
+        Command::Asm => {
+            let object = assemble(&read_text(&options.input)?);
+            match options.format {
+                Some(Format::Asm) => to_text(&load(&object.to_bytes())).into_bytes(),
+                Some(Format::Json) => {
+                    let labels = object.symbols.iter().map(|(label, pc)| (label.clone(), *pc)).collect();
+                    to_json(Program { instrs: load(&object.to_bytes()), labels, ..Program::default() }, &options)
+                },
+                _ => object.to_bytes(),
+            }
+        },
+        Command::Disasm => {
+            let program = load(&read_input(&options.input)?);
+            match options.format {
+                Some(Format::Bin) => grumpy_isa::encode_program(&program),
+                Some(Format::Json) => to_json(Program::from_instrs(&program), &options),
+                _ => to_text(&program).into_bytes(),
+            }
+        },
+        Command::Run => {
+            let program = load(&read_input(&options.input)?);
+            format!("{:?}\n", run(program, &options)).into_bytes()
+        },
+        Command::Build => {
+            let executable = assemble(&to_text(&compile(&read_text(&options.input)?))).to_bytes();
+            format!("{:?}\n", run(load(&executable), &options)).into_bytes()
+        },
+    };
+    write_output(&output, &bytes)
+}
@@ -0,0 +1,314 @@
+use std::process;
+
+pub const USAGE: &str = "usage: grumpy <command> [options] <file | ->
+
+commands:
+  compile <file.gpy>    compile Grumpy to assembly
+  asm <file.s>          assemble into an executable .o
+  disasm <file.o>       turn an executable back into assembly
+  run <file.o>          run an executable
+  build <file.gpy>      compile, assemble and run
+
+options:
+  -o <out>              write to <out>, - for stdout
+  --format <asm|bin|json>
+                        what compile, asm and disasm write
+  --vm <gc|vm>          the VM that runs the program, gc by default
+  --heap-size <n>       heap size in values, 1024 by default
+  --heap-max <n>        most values gc grows the heap to, 1048576 by default
+  --heap-growth <f>     how many times over gc grows or shrinks the heap, 2 by
+                        default
+  --heap-live <r>       at most how full gc leaves the heap after collecting,
+                        0.5 by default
+  --fuel <n>            give up after running n instructions
+  --trace               print every instruction to stderr as it runs
+  --collector <copying|mark-compact|generational|incremental>
+                        the collector gc runs, copying by default
+  --gc-verify           check the heap after every collection
+  --gc-stress           collect before every alloc, and --gc-verify
+  --gc-stats <file>     write every collection to <file> as a line of JSON
+  --threads <green|os>  run gc threads taking turns on one OS thread (the
+                        default) or each on an OS thread of its own
+  --sched <round-robin|random|priority>
+                        how gc picks the next thread, round-robin by default
+  --quantum <n>         most instructions a thread runs at a time, 3 by default
+  --seed <n>            seed for --sched random, printed when not given
+  --priority <p,...>    priorities of threads 0, 1, ... for --sched priority";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Compile,
+    Asm,
+    Disasm,
+    Run,
+    Build,
+}
+
+/// How a program is written out: assembly text, an executable `.o` or JSON.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Asm,
+    Bin,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Machine {
+    Gc,
+    Vm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Collector {
+    Copying,
+    MarkCompact,
+    Generational,
+    Incremental,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Threads {
+    Green,
+    Os,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sched {
+    RoundRobin,
+    Random,
+    Priority,
+}
+
+/// The command line, shared by every subcommand.
+pub struct Options {
+    pub command: Command,
+    pub input: String,
+    pub output: Option<String>,
+    pub format: Option<Format>,
+    pub machine: Machine,
+    pub heap_size: Option<u32>,
+    pub heap_max: Option<u32>,
+    pub heap_growth: Option<f64>,
+    pub heap_live: Option<f64>,
+    pub fuel: Option<u64>,
+    pub trace: bool,
+    pub collector: Collector,
+    pub gc_stress: bool,
+    pub gc_verify: bool,
+    pub gc_stats: Option<String>,
+    pub threads: Threads,
+    pub sched: Sched,
+    pub quantum: Option<u32>,
+    pub seed: Option<u64>,
+    pub priorities: Vec<u32>,
+}
+
+impl Options {
+    fn runs_program(&self) -> bool {
+        self.command == Command::Run || self.command == Command::Build
+    }
+}
+
+pub fn usage_error(msg: &str) -> ! {
+    eprintln!("grumpy: {}\n{}", msg, USAGE);
+    process::exit(2);
+}
+
+fn number<T: std::str::FromStr>(flag: &str, value: Option<&String>) -> T {
+    match value.map(|v| v.parse::<T>()) {
+        Some(Ok(n)) => n,
+        _ => usage_error(&format!("{} needs a number", flag)),
+    }
+}
+
+pub fn parse_args(args: &[String]) -> Options {
+    let command = match args.get(1).map(|c| &**c) {
+        Some("compile") => Command::Compile,
+        Some("asm") => Command::Asm,
+        Some("disasm") => Command::Disasm,
+        Some("run") => Command::Run,
+        Some("build") => Command::Build,
+        Some(other) => usage_error(&format!("unknown command {}", other)),
+        None => usage_error("no command given"),
+    };
+
+    let mut options = Options { command, input: String::new(), output: None, format: None, machine: Machine::Gc,
+                                heap_size: None, heap_max: None, heap_growth: None, heap_live: None, fuel: None, trace: false, collector: Collector::Copying,
+                                gc_stress: false, gc_verify: false, gc_stats: None,
+                                threads: Threads::Green,
+                                sched: Sched::RoundRobin,
+                                quantum: None, seed: None, priorities: Vec::new() };
+    let mut inputs = Vec::new();
+    let mut vm_flags = Vec::new();
+    let mut sched_flags = Vec::new();
+    let mut os_flags = Vec::new();
+    let mut gc_flags = Vec::new();
+    let mut iter = args.iter().skip(2);
+    while let Some(arg) = iter.next() {
+        match &**arg {
+            "-o" => match iter.next() {
+                Some(path) => options.output = Some(path.clone()),
+                None => usage_error("-o needs a path"),
+            },
+            "--format" => options.format = match iter.next().map(|f| &**f) {
+                Some("asm") => Some(Format::Asm),
+                Some("bin") => Some(Format::Bin),
+                Some("json") => Some(Format::Json),
+                _ => usage_error("--format is asm, bin or json"),
+            },
+            "--vm" => {
+                options.machine = match iter.next().map(|m| &**m) {
+                    Some("gc") => Machine::Gc,
+                    Some("vm") => Machine::Vm,
+                    _ => usage_error("--vm is gc or vm"),
+                };
+                vm_flags.push(arg);
+            },
+            "--heap-size" => {
+                options.heap_size = Some(number(arg, iter.next()));
+                vm_flags.push(arg);
+            },
+            "--heap-max" => {
+                options.heap_max = Some(number(arg, iter.next()));
+                vm_flags.push(arg);
+                gc_flags.push(arg);
+            },
+            "--heap-growth" => {
+                options.heap_growth = match number(arg, iter.next()) {
+                    factor if factor > 1.0 => Some(factor),
+                    _ => usage_error("--heap-growth has to be more than 1"),
+                };
+                vm_flags.push(arg);
+                gc_flags.push(arg);
+            },
+            "--heap-live" => {
+                options.heap_live = match number(arg, iter.next()) {
+                    ratio if ratio > 0.0 && ratio <= 1.0 => Some(ratio),
+                    _ => usage_error("--heap-live is more than 0 and at most 1"),
+                };
+                vm_flags.push(arg);
+                gc_flags.push(arg);
+            },
+            "--fuel" => {
+                options.fuel = Some(number(arg, iter.next()));
+                vm_flags.push(arg);
+            },
+            "--trace" => {
+                options.trace = true;
+                vm_flags.push(arg);
+            },
+            "--collector" => {
+                options.collector = match iter.next().map(|c| &**c) {
+                    Some("copying") => Collector::Copying,
+                    Some("mark-compact") => Collector::MarkCompact,
+                    Some("generational") => Collector::Generational,
+                    Some("incremental") => Collector::Incremental,
+                    _ => usage_error("--collector is copying, mark-compact, generational or incremental"),
+                };
+                vm_flags.push(arg);
+                gc_flags.push(arg);
+            },
+            "--gc-verify" => {
+                options.gc_verify = true;
+                vm_flags.push(arg);
+                gc_flags.push(arg);
+            },
+            "--gc-stress" => {
+                options.gc_stress = true;
+                options.gc_verify = true;
+                vm_flags.push(arg);
+                gc_flags.push(arg);
+            },
+            "--gc-stats" => {
+                match iter.next() {
+                    Some(path) => options.gc_stats = Some(path.clone()),
+                    None => usage_error("--gc-stats needs a path"),
+                }
+                vm_flags.push(arg);
+                gc_flags.push(arg);
+            },
+            "--threads" => {
+                options.threads = match iter.next().map(|t| &**t) {
+                    Some("green") => Threads::Green,
+                    Some("os") => Threads::Os,
+                    _ => usage_error("--threads is green or os"),
+                };
+                vm_flags.push(arg);
+                if options.threads == Threads::Os {
+                    os_flags.push(arg);
+                }
+            },
+            "--sched" => {
+                options.sched = match iter.next().map(|s| &**s) {
+                    Some("round-robin") => Sched::RoundRobin,
+                    Some("random") => Sched::Random,
+                    Some("priority") => Sched::Priority,
+                    _ => usage_error("--sched is round-robin, random or priority"),
+                };
+                vm_flags.push(arg);
+                sched_flags.push(arg);
+            },
+            "--quantum" => {
+                options.quantum = match number(arg, iter.next()) {
+                    0 => usage_error("--quantum must be at least 1"),
+                    n => Some(n),
+                };
+                vm_flags.push(arg);
+                sched_flags.push(arg);
+            },
+            "--seed" => {
+                options.seed = Some(number(arg, iter.next()));
+                vm_flags.push(arg);
+                sched_flags.push(arg);
+            },
+            "--priority" => {
+                let list = iter.next().unwrap_or_else(|| usage_error("--priority needs a list of numbers"));
+                options.priorities = list.split(',').map(|p| number(arg, Some(&p.to_string()))).collect();
+                vm_flags.push(arg);
+                sched_flags.push(arg);
+            },
+            "-" => inputs.push(arg.clone()),
+            flag if flag.starts_with('-') => usage_error(&format!("unknown option {}", flag)),
+            _ => inputs.push(arg.clone()),
+        }
+    }
+
+    match inputs.len() {
+        0 => usage_error("no input file"),
+        1 => options.input = inputs.remove(0),
+        _ => usage_error("only one input file can be given"),
+    }
+    if options.runs_program() && options.format.is_some() {
+        usage_error("--format only applies to compile, asm and disasm");
+    }
+    if !options.runs_program() {
+        if let Some(flag) = vm_flags.first() {
+            usage_error(&format!("{} only applies to run and build", flag));
+        }
+    }
+    if options.machine == Machine::Vm {
+        if let Some(flag) = sched_flags.first().or(os_flags.first()) {
+            usage_error(&format!("{} only applies to --vm gc, vm runs one thread", flag));
+        }
+        if let Some(flag) = gc_flags.first() {
+            usage_error(&format!("{} only applies to --vm gc, vm does not collect", flag));
+        }
+    }
+    if options.threads == Threads::Os {
+        if let Some(flag) = sched_flags.first() {
+            usage_error(&format!("{} only applies to green threads, the OS schedules --threads os", flag));
+        }
+    }
+    if let (Some(max), Some(size)) = (options.heap_max, options.heap_size) {
+        if max < size {
+            usage_error("--heap-max is smaller than --heap-size");
+        }
+    }
+    if options.seed.is_some() && options.sched != Sched::Random {
+        usage_error("--seed only applies to --sched random");
+    }
+    if !options.priorities.is_empty() && options.sched != Sched::Priority {
+        usage_error("--priority only applies to --sched priority");
+    }
+    options
+}
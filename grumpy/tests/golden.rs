@@ -0,0 +1,496 @@
+//! Golden tests over the cases the shell scripts drive, one check per stage:
+//!
+//! * `asm`: every `a1_cargo/assemble/tests/*.s` against the `.o` bytes in its
+//!   `.expected`, `tests/link` against `main.expected`, and every
+//!   `a3_cargo/gc/src/tests/*.s` against its `.o`
+//! * `compile`: every `a5_cargo/Op/tests/*.gpy` against its `.s`
+//! * `run`: every given `.o` in `a3_cargo/gc/src/tests` and `a5_cargo/Op/tests`
+//!   on the gc VM against its `.expected` (or `.other_expected`)
+//! * `build`: every `.gpy` compiled, assembled and run, against its `.expected`
+//!
+//! `UPDATE_EXPECT=1 cargo test` writes what each stage produced back to its
+//! golden instead of comparing. `run` only reads goldens, and cases in
+//! `KNOWN_FAILURES` are never rewritten.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+
+use grumpy_isa::decode_program;
+
+//Enough for every case that terminates
+const FUEL: &str = "10000000";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    Asm,
+    Compile,
+    Run,
+    Build,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Stage::Asm => "asm",
+            Stage::Compile => "compile",
+            Stage::Run => "run",
+            Stage::Build => "build",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Cases that do not match their goldens yet, with the reason. A case that
+/// starts passing has to be taken off this list.
+const KNOWN_FAILURES: &[(&str, Stage, &str)] = &[
+    ("applam", Stage::Compile, LAMBDAS), ("applam", Stage::Build, LAMBDAS),
+    ("applam2", Stage::Compile, LAMBDAS), ("applam2", Stage::Build, LAMBDAS),
+    ("applam3", Stage::Compile, LAMBDAS), ("applam3", Stage::Build, LAMBDAS),
+    ("applam4", Stage::Compile, LAMBDAS), ("applam4", Stage::Build, LAMBDAS),
+    ("applam5", Stage::Compile, LAMBDAS), ("applam5", Stage::Build, LAMBDAS),
+    ("applam6", Stage::Compile, LAMBDAS), ("applam6", Stage::Build, LAMBDAS),
+    ("dotwice", Stage::Compile, LAMBDAS), ("dotwice", Stage::Build, LAMBDAS),
+    ("pair", Stage::Compile, PAIRS), ("pair", Stage::Build, PAIRS),
+    ("pairsum", Stage::Compile, PAIRS), ("pairsum", Stage::Build, PAIRS),
+    ("match", Stage::Compile, PAIRS), ("match", Stage::Build, PAIRS),
+    ("match2", Stage::Compile, PAIRS), ("match2", Stage::Build, PAIRS),
+    ("lists", Stage::Compile, PAIRS), ("lists", Stage::Build, PAIRS),
+    ("mu", Stage::Compile, PAIRS), ("mu", Stage::Build, PAIRS),
+    ("print", Stage::Compile, PRINT), ("print", Stage::Build, PRINT),
+    ("conc-spawn1", Stage::Compile, PRINT), ("conc-spawn1", Stage::Build, PRINT),
+    ("conc-spawn2", Stage::Compile, PRINT), ("conc-spawn2", Stage::Build, PRINT),
+    ("conc-spawn3", Stage::Compile, PRINT), ("conc-spawn3", Stage::Build, PRINT),
+    ("fact", Stage::Build, TAIL_RET), ("fact2", Stage::Build, TAIL_RET), ("fib", Stage::Build, TAIL_RET), ("fib-memo", Stage::Build, TAIL_RET),
+    ("heap", Stage::Build, TAIL_RET), ("heap2", Stage::Build, TAIL_RET), ("heap3", Stage::Build, TAIL_RET),
+    ("funptr3", Stage::Build, "Op pushes the label of a function passed as an argument without defining it"),
+];
+
+const LAMBDAS: &str = "Op does not compile lam, app or function types yet";
+const PAIRS: &str = "Op does not compile pairs, sums or recursive types yet";
+const PRINT: &str = "Op does not compile print or spawn of a lam yet";
+const TAIL_RET: &str = "Op emits a ret right after every call, so a function returns before it uses what the call gave back";
+
+/// Cases that must fail to produce a result at all, like the `fail` lists
+/// in test-pa4.sh and run-pa3.sh.
+const NO_RESULT: &[&str] = &["conc-deadlock", "conc-lock-leak", "conc-join-cycle", "conc-recv"];
+
+fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap().to_path_buf()
+}
+
+fn updating() -> bool {
+    env::var("UPDATE_EXPECT").map(|v| v == "1").unwrap_or(false)
+}
+
+fn cases(dir: &Path, ext: &str) -> Vec<PathBuf> {
+    let mut cases: Vec<PathBuf> = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == ext))
+        .collect();
+    cases.sort();
+    cases
+}
+
+fn name(path: &Path) -> String {
+    path.file_stem().unwrap().to_string_lossy().into_owned()
+}
+
+fn read_text(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok()
+}
+
+//Assembly one instruction per line, or the raw bytes if they do not decode
+fn disassemble(bytes: &[u8]) -> String {
+    match decode_program(bytes) {
+        Ok(program) => program.iter().map(|instr| format!("{}\n", instr)).collect(),
+        Err(err) => format!("<{} bytes that do not decode: {}>\n", bytes.len(), err),
+    }
+}
+
+//Every line that differs, marked - for the golden and + for what was produced
+fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let mut out = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if e == a => {},
+            (e, a) => {
+                let e = e.map(|e| format!("-{}", e));
+                let a = a.map(|a| format!("+{}", a));
+                let sides: Vec<String> = e.into_iter().chain(a).collect();
+                out += &format!("  {:>4} {}\n", i + 1, sides.join("  "));
+            },
+        }
+    }
+    out
+}
+
+/// Runs the driver the way the shell scripts run each tool, one process per
+/// stage, so printed output, panics and running out of fuel show up as they
+/// do on the command line.
+fn grumpy(args: &[&str], input: &[u8]) -> Result<Vec<u8>, String> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_grumpy"))
+        .args(args).arg("-")
+        .env_remove("RUST_BACKTRACE")
+        .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped())
+        .spawn().unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        //The message and not the source it quotes or the backtrace note
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let mut lines = stderr.lines().filter(|l| !l.trim().is_empty() && !l.starts_with("note:"));
+        let msg = match lines.next().map(|l| l.trim_start_matches("grumpy: ")) {
+            Some(first) if first.contains("panicked at") => lines.next().unwrap_or(first),
+            Some(first) => first,
+            None => "",
+        };
+        Err(format!("{} ({})", msg, output.status))
+    }
+}
+
+fn compile(source: &str) -> Result<String, String> {
+    grumpy(&["compile"], source.as_bytes()).map(|text| String::from_utf8_lossy(&text).into_owned())
+}
+
+fn assemble(source: &str) -> Result<Vec<u8>, String> {
+    grumpy(&["asm", "-o", "-"], source.as_bytes())
+}
+
+fn run(executable: &[u8]) -> Result<String, String> {
+    run_with(executable, &[])
+}
+
+fn run_with(executable: &[u8], flags: &[&str]) -> Result<String, String> {
+    let args = [&["run", "--fuel", FUEL], flags].concat();
+    grumpy(&args, executable).map(|out| String::from_utf8_lossy(&out).trim_end().to_string())
+}
+
+fn known(case: &str, stage: Stage) -> bool {
+    KNOWN_FAILURES.iter().any(|(c, s, _)| *c == case && *s == stage)
+}
+
+/// Every check made by one test, reported together once they have all run.
+#[derive(Default)]
+struct Report {
+    checks: Vec<(String, Stage, Result<(), String>)>,
+}
+
+impl Report {
+    fn check(&mut self, case: &str, stage: Stage, result: Result<(), String>) {
+        self.checks.push((case.to_string(), stage, result));
+    }
+
+    //A failure not on KNOWN_FAILURES fails the test, and so does a known failure that passes
+    fn finish(self) {
+        let mut problems = Vec::new();
+        for (case, stage, result) in &self.checks {
+            match (result, known(case, *stage)) {
+                (Err(msg), false) => problems.push(format!("{} [{}]: {}", case, stage, msg)),
+                (Ok(()), true) => problems.push(format!("{} [{}]: passes now, take it off KNOWN_FAILURES", case, stage)),
+                _ => {},
+            }
+        }
+        if !problems.is_empty() {
+            panic!("{} of {} golden checks failed:\n\n{}", problems.len(), self.checks.len(), problems.join("\n"));
+        }
+    }
+}
+
+//Goldens end without a newline, and a result may match any of them
+fn compare_text(case: &str, stage: Stage, goldens: &[PathBuf], actual: &str) -> Result<(), String> {
+    let expected: Vec<String> = goldens.iter().filter_map(|golden| read_text(golden)).collect();
+    if expected.iter().any(|e| e.trim_end() == actual.trim_end()) {
+        return Ok(());
+    }
+    if updating() && stage != Stage::Run && !known(case, stage) {
+        fs::write(&goldens[0], actual).unwrap();
+        return Ok(());
+    }
+    match expected.first() {
+        Some(first) => Err(format!("differs from {}\n{}", goldens[0].display(), diff(first, actual))),
+        None => Err(format!("{} is missing, make it with UPDATE_EXPECT=1", goldens[0].display())),
+    }
+}
+
+fn compare_bytes(case: &str, golden: &Path, actual: &[u8]) -> Result<(), String> {
+    let expected = fs::read(golden).ok();
+    if expected.as_deref() == Some(actual) {
+        return Ok(());
+    }
+    if updating() && !known(case, Stage::Asm) {
+        fs::write(golden, actual).unwrap();
+        return Ok(());
+    }
+    match expected {
+        Some(expected) => Err(format!("differs from {}\n{}", golden.display(), diff(&disassemble(&expected), &disassemble(actual)))),
+        None => Err(format!("{} is missing, make it with UPDATE_EXPECT=1", golden.display())),
+    }
+}
+
+fn results(case: &Path) -> Vec<PathBuf> {
+    vec![case.with_extension("expected"), case.with_extension("other_expected")]
+}
+
+//A NO_RESULT case passes only when running it fails
+fn check_result(case: &Path, stage: Stage, result: Result<String, String>) -> Result<(), String> {
+    let name = name(case);
+    match result {
+        Ok(result) if NO_RESULT.contains(&&*name) => Err(format!("should not have a result, got {}", result)),
+        Err(_) if NO_RESULT.contains(&&*name) => Ok(()),
+        Ok(result) => compare_text(&name, stage, &results(case), &result),
+        Err(err) => Err(err),
+    }
+}
+
+fn build(source: &str) -> Result<String, String> {
+    let text = compile(source).map_err(|err| format!("compile: {}", err))?;
+    let executable = assemble(&text).map_err(|err| format!("asm: {}", err))?;
+    run(&executable).map_err(|err| format!("run: {}", err))
+}
+
+#[test]
+fn assembler_goldens() {
+    let dir = root().join("a1_cargo/assemble/tests");
+    let mut report = Report::default();
+    for case in cases(&dir, "s") {
+        let name = name(&case);
+        let result = assemble(&read_text(&case).unwrap())
+            .and_then(|bytes| compare_bytes(&name, &case.with_extension("expected"), &bytes));
+        report.check(&name, Stage::Asm, result);
+    }
+
+    let link = dir.join("link");
+    let options = assemble::Options { relocatable: true, ..assemble::Options::default() };
+    let objects: Result<Vec<_>, String> = ["main.s", "lib.s"].iter().map(|file| {
+        let source = read_text(&link.join(file)).unwrap();
+        assemble::assemble(&source, &options).map(|assembly| (file.to_string(), assembly.object)).map_err(|err| err.to_string())
+    }).collect();
+    let result = objects.and_then(|objects| assemble::link::link(&objects).map_err(|errors| errors.join(", ")))
+        .and_then(|bytes| compare_bytes("link", &link.join("main.expected"), &bytes));
+    report.check("link", Stage::Asm, result);
+    report.finish();
+}
+
+#[test]
+fn compiler_goldens() {
+    let dir = root().join("a5_cargo/Op/tests");
+    let mut report = Report::default();
+    for case in cases(&dir, "gpy") {
+        let name = name(&case);
+        let source = read_text(&case).unwrap();
+        let result = compile(&source).and_then(|text| compare_text(&name, Stage::Compile, &[case.with_extension("s")], &text));
+        report.check(&name, Stage::Compile, result);
+        report.check(&name, Stage::Build, check_result(&case, Stage::Build, build(&source)));
+    }
+    report.finish();
+}
+
+//The Op test programs that come assembled already, run on the gc VM like every other .o
+#[test]
+fn op_objects_on_gc() {
+    let dir = root().join("a5_cargo/Op/tests");
+    let mut report = Report::default();
+    for case in cases(&dir, "o") {
+        let result = check_result(&case, Stage::Run, run(&fs::read(&case).unwrap()));
+        report.check(&name(&case), Stage::Run, result);
+    }
+    report.finish();
+}
+
+#[test]
+fn gc_goldens() {
+    let dir = root().join("a3_cargo/gc/src/tests");
+    let mut report = Report::default();
+    for case in cases(&dir, "s") {
+        let result = assemble(&read_text(&case).unwrap())
+            .and_then(|bytes| compare_bytes(&name(&case), &case.with_extension("o"), &bytes));
+        report.check(&name(&case), Stage::Asm, result);
+    }
+    for case in cases(&dir, "o") {
+        let result = check_result(&case, Stage::Run, run(&fs::read(&case).unwrap()));
+        report.check(&name(&case), Stage::Run, result);
+    }
+    report.finish();
+}
+
+//Every gc case again, run with another collector
+fn collector_goldens(collector: &str, flags: &[&str]) {
+    let dir = root().join("a3_cargo/gc/src/tests");
+    let flags = [&["--collector", collector], flags].concat();
+    let mut report = Report::default();
+    for case in cases(&dir, "o") {
+        let result = check_result(&case, Stage::Run, run_with(&fs::read(&case).unwrap(), &flags));
+        report.check(&name(&case), Stage::Run, result);
+    }
+    report.finish();
+}
+
+#[test]
+fn mark_compact_goldens() {
+    collector_goldens("mark-compact", &[]);
+}
+
+#[test]
+fn generational_goldens() {
+    collector_goldens("generational", &[]);
+}
+
+#[test]
+fn incremental_goldens() {
+    collector_goldens("incremental", &[]);
+}
+
+//Collecting before every alloc and checking the heap after, with every collector
+#[test]
+fn stress_goldens() {
+    for collector in ["copying", "mark-compact", "generational", "incremental"] {
+        collector_goldens(collector, &["--gc-stress"]);
+    }
+}
+
+/// Cases whose result depends on how the threads interleave, which OS
+/// threads do not do the same way twice.
+const INTERLEAVED: &[&str] = &["conc-race", "conc-spawn1", "conc-spawn2", "conc-spawn3"];
+
+#[test]
+fn os_thread_goldens() {
+    let dir = root().join("a3_cargo/gc/src/tests");
+    let mut report = Report::default();
+    for case in cases(&dir, "o") {
+        if INTERLEAVED.contains(&&*name(&case)) {
+            continue;
+        }
+        let result = check_result(&case, Stage::Run, run_with(&fs::read(&case).unwrap(), &["--threads", "os"]));
+        report.check(&name(&case), Stage::Run, result);
+    }
+    report.finish();
+}
+
+//Deadlocks stop with their own status and not by running out of fuel, even while another thread spins
+#[test]
+fn deadlocks() {
+    let dir = root().join("a3_cargo/gc/src/tests");
+    for threads in ["green", "os"] {
+        for case in ["conc-deadlock", "conc-lock-leak", "conc-join-cycle"] {
+            let result = run_with(&fs::read(dir.join(case).with_extension("o")).unwrap(), &["--threads", threads]);
+            match result {
+                Err(err) => assert!(err.starts_with("run: deadlock") && err.ends_with("(exit status: 3)"), "{} {}: {}", case, threads, err),
+                Ok(result) => panic!("{} {}: should deadlock, got {}", case, threads, result),
+            }
+        }
+    }
+}
+
+//The a2 VM has no threads, channels, mutexes or print, and says so instead of running them
+#[test]
+fn vm_rejects_gc_only_instructions() {
+    let dir = root().join("a3_cargo/gc/src/tests");
+    for (case, instr) in [("conc-cas", "spawn"), ("conc-channel", "chan")] {
+        let executable = fs::read(dir.join(format!("{}.o", case))).unwrap();
+        match run_with(&executable, &["--vm", "vm"]) {
+            Err(err) => assert!(err.starts_with(&format!("run: {} is not supported by this VM", instr)), "{}: {}", case, err),
+            Ok(result) => panic!("{}: the a2 VM ran it and got {}", case, result),
+        }
+    }
+    let print = assemble("setframe 0\npush 1\nprint\nhalt\n").unwrap();
+    assert_eq!(run_with(&print, &["--vm", "vm"]).unwrap_err(),
+               "run: print is not supported by this VM, run it on the gc VM (exit status: 1)");
+}
+
+//The heap grows past where it started for as long as it may, and alloc traps once it may not
+#[test]
+fn heap_growth() {
+    let grow = fs::read(root().join("a3_cargo/gc/src/tests/heap-grow.o")).unwrap();
+    let fact2 = fs::read(root().join("a3_cargo/gc/src/tests/fact2.o")).unwrap();
+    match run_with(&fact2, &["--heap-max", "1024"]) {
+        Err(err) => assert!(err.starts_with("run: out of memory"), "fact2: {}", err),
+        Ok(result) => panic!("fact2: should run out of memory in a heap that cannot grow, got {}", result),
+    }
+    for collector in ["copying", "mark-compact", "generational", "incremental"] {
+        for threads in ["green", "os"] {
+            let flags = ["--collector", collector, "--threads", threads];
+            assert_eq!(run_with(&grow, &flags), Ok("Vi32(45150)".to_string()), "{} {}", collector, threads);
+            match run_with(&grow, &[&flags[..], &["--heap-max", "2048"]].concat()) {
+                Err(err) => assert!(err.starts_with("run: out of memory"), "{} {}: {}", collector, threads, err),
+                Ok(result) => panic!("{} {}: should run out of memory, got {}", collector, threads, result),
+            }
+        }
+    }
+}
+
+//What a line of --gc-stats says for a field that is not a list
+fn field<'a>(line: &'a str, name: &str) -> &'a str {
+    let key = format!("\"{}\":", name);
+    let start = line.find(&key).unwrap_or_else(|| panic!("no {} in {}", name, line)) + key.len();
+    let value = &line[start..];
+    &value[..value.find([',', '}']).unwrap()]
+}
+
+//One line per collection, numbered in order, each leaving the heap within the size picked for it
+#[test]
+fn gc_stats() {
+    let grow = fs::read(root().join("a3_cargo/gc/src/tests/heap-grow.o")).unwrap();
+    for collector in ["copying", "mark-compact", "generational", "incremental"] {
+        for threads in ["green", "os"] {
+            let path = env::temp_dir().join(format!("grumpy-gc-stats-{}-{}-{}.jsonl", process::id(), collector, threads));
+            let flags = ["--heap-size", "256", "--collector", collector, "--threads", threads, "--gc-stats", path.to_str().unwrap()];
+            assert_eq!(run_with(&grow, &flags), Ok("Vi32(45150)".to_string()), "{} {}", collector, threads);
+            let stats = fs::read_to_string(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+            assert!(stats.lines().count() > 1, "{} {}: {:?}", collector, threads, stats);
+            for (i, line) in stats.lines().enumerate() {
+                let number = |name| field(line, name).parse::<usize>().unwrap_or_else(|_| panic!("{} in {}", name, line));
+                assert_eq!(number("collection"), i + 1, "{}", line);
+                assert_eq!(field(line, "collector"), format!("\"{}\"", collector), "{}", line);
+                assert!(["\"heap full\"", "\"nursery full\"", "\"half full\""].contains(&field(line, "trigger")), "{}", line);
+                let kinds: &[&str] = match collector {
+                    "generational" => &["\"minor\"", "\"major\""],
+                    "incremental" => &["\"cycle started\"", "\"cycle finished\""],
+                    _ => &["\"full\""],
+                };
+                assert!(kinds.contains(&field(line, "kind")), "{}", line);
+                assert!(line.contains("\"roots\":[") && number("pause") > 0, "{}", line);
+                assert!(number("slots_after") <= number("resized_to") && number("objects_after") <= number("slots_after"), "{}", line);
+                assert_eq!(number("bytes_copied"), number("values_copied") * 8, "{}", line);
+                if collector == "copying" {
+                    assert_eq!(number("values_copied"), number("slots_after"), "{}", line);
+                }
+            }
+        }
+    }
+    let missing = env::temp_dir().join("grumpy-gc-stats-missing").join("stats.jsonl");
+    match run_with(&grow, &["--gc-stats", missing.to_str().unwrap()]) {
+        Err(err) => assert!(err.starts_with("run: GC: cannot write the stats to"), "{}", err),
+        Ok(result) => panic!("should not run without somewhere to write the stats, got {}", result),
+    }
+}
+
+//The same seed has to give the same interleaving, and the locked counter has to come out right under every one
+#[test]
+fn schedules() {
+    let dir = root().join("a3_cargo/gc/src/tests");
+    let race = fs::read(dir.join("conc-race.o")).unwrap();
+    let locked = fs::read(dir.join("conc-mutex.o")).unwrap();
+    let schedules: &[&[&str]] = &[
+        &["--quantum", "1"],
+        &["--quantum", "50"],
+        &["--sched", "random", "--seed", "7"],
+        &["--sched", "random", "--seed", "8", "--quantum", "10"],
+        &["--sched", "priority", "--priority", "0,2,1"],
+    ];
+    for schedule in schedules {
+        let args = [&["run", "--fuel", "10000000"], *schedule].concat();
+        let first = grumpy(&args, &race);
+        assert!(first.is_ok(), "{:?}: {:?}", schedule, first);
+        assert_eq!(first, grumpy(&args, &race), "{:?} ran conc-race differently twice", schedule);
+        assert_eq!(grumpy(&args, &locked), Ok(b"Vi32(20)\n".to_vec()), "{:?} lost an update under the mutex", schedule);
+    }
+}
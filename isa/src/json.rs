@@ -0,0 +1,211 @@
+//! A JSON form of a program for tools that would rather not decode `.o`
+//! files. A program looks like
+//!
+//! ```text
+//! {
+//!   "grumpy": 1,
+//!   "metadata": {"producer":"Op","source":"fact.gpy"},
+//!   "labels": {"Lmain":4},
+//!   "instructions": [
+//!     {"op":"setframe","arg":0},
+//!     {"op":"push","value":{"type":"label","value":"Lmain"}},
+//!     {"op":"binary","arg":"+"},
+//!     ...
+//!   ]
+//! }
+//! ```
+//!
+//! Labels are kept out of the instruction list and map to the pc of the
+//! instruction they name. Values are `{"type": t, "value": v}` where `t` is
+//! one of `unit`, `i32`, `bool`, `loc`, `undef` or `label`, and `value` is
+//! left out for `unit` and `undef`.
+
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+use crate::{Binop, Instr, Unop, Val};
+
+/// The version written to and expected in the `grumpy` field.
+pub const VERSION: u64 = 1;
+
+/// A program with its labels pulled out of the instruction stream.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Program {
+    pub instrs: Vec<Instr>,
+    pub labels: BTreeMap<String, u32>,
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl Program {
+    /// Takes instructions as a compiler produces them, with `Label`s in
+    /// place, and gives each label the pc of the instruction after it.
+    pub fn from_instrs(program: &[Instr]) -> Program {
+        let mut result = Program::default();
+        for instr in program {
+            match instr {
+                Instr::Label(label) => { result.labels.insert(label.clone(), result.instrs.len() as u32); },
+                instr => result.instrs.push(instr.clone()),
+            }
+        }
+        result
+    }
+
+    /// The instructions with every label put back where it was.
+    pub fn with_labels(&self) -> Vec<Instr> {
+        let mut by_pc: BTreeMap<u32, Vec<&String>> = BTreeMap::new();
+        for (label, pc) in &self.labels {
+            by_pc.entry(*pc).or_default().push(label);
+        }
+        let mut program = Vec::new();
+        for pc in 0..=self.instrs.len() {
+            for label in by_pc.get(&(pc as u32)).into_iter().flatten() {
+                program.push(Instr::Label(label.to_string()));
+            }
+            if let Some(instr) = self.instrs.get(pc) {
+                program.push(instr.clone());
+            }
+        }
+        program
+    }
+
+    /// The instructions ready to run, with every pushed label replaced by
+    /// its location.
+    pub fn resolved(&self) -> Result<Vec<Instr>, String> {
+        self.instrs.iter().map(|instr| match instr {
+            Instr::Push(Val::Vlabel(label)) => match self.labels.get(label) {
+                Some(pc) => Ok(Instr::Push(Val::Vloc(*pc))),
+                None => Err(format!("{} is not a label", label)),
+            },
+            instr => Ok(instr.clone()),
+        }).collect()
+    }
+
+    pub fn to_json(&self) -> String {
+        let instrs: Vec<String> = self.instrs.iter().map(|instr| format!("    {}", instr_to_json(instr))).collect();
+        format!("{{\n  \"grumpy\": {},\n  \"metadata\": {},\n  \"labels\": {},\n  \"instructions\": [\n{}\n  ]\n}}\n",
+                VERSION, json!(self.metadata), json!(self.labels), instrs.join(",\n"))
+    }
+
+    pub fn from_json(text: &str) -> Result<Program, String> {
+        let root: Value = serde_json::from_str(text).map_err(|err| format!("JSON: {}", err))?;
+        let root = root.as_object().ok_or("JSON: a program is an object")?;
+        match root.get("grumpy").and_then(Value::as_u64) {
+            Some(VERSION) => {},
+            Some(other) => return Err(format!("JSON: version {} is not supported", other)),
+            None => return Err("JSON: not a Grumpy program, the grumpy version is missing".to_string()),
+        }
+
+        let mut program = Program::default();
+        for (key, value) in object_field(root, "metadata")?.into_iter().flatten() {
+            let value = value.as_str().ok_or_else(|| format!("JSON: metadata {} is not a string", key))?;
+            program.metadata.insert(key.clone(), value.to_string());
+        }
+        for (label, pc) in object_field(root, "labels")?.into_iter().flatten() {
+            let pc = pc.as_u64().ok_or_else(|| format!("JSON: label {} is not a pc", label))?;
+            program.labels.insert(label.clone(), pc as u32);
+        }
+        let instrs = root.get("instructions").and_then(Value::as_array).ok_or("JSON: instructions are missing")?;
+        for (pc, instr) in instrs.iter().enumerate() {
+            program.instrs.push(instr_from_json(instr).map_err(|err| format!("JSON: instruction {}: {}", pc, err))?);
+        }
+        Ok(program)
+    }
+}
+
+/// True if `bytes` look like a JSON program rather than a binary `.o`.
+pub fn is_json(bytes: &[u8]) -> bool {
+    bytes.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{')
+}
+
+/// Reads a program in either format, ready to run.
+pub fn load_program(bytes: &[u8]) -> Result<Vec<Instr>, String> {
+    if is_json(bytes) {
+        let text = std::str::from_utf8(bytes).map_err(|err| format!("JSON: {}", err))?;
+        Program::from_json(text)?.resolved()
+    } else {
+        crate::decode_program(bytes)
+    }
+}
+
+//Metadata and labels may be left out entirely
+fn object_field<'a>(root: &'a Map<String, Value>, name: &str) -> Result<Option<&'a Map<String, Value>>, String> {
+    match root.get(name) {
+        Some(value) => value.as_object().map(Some).ok_or_else(|| format!("JSON: {} is not an object", name)),
+        None => Ok(None),
+    }
+}
+
+fn val_to_json(val: &Val) -> Value {
+    match val {
+        Val::Vunit => json!({"type": "unit"}),
+        Val::Vi32(i) => json!({"type": "i32", "value": i}),
+        Val::Vbool(b) => json!({"type": "bool", "value": b}),
+        Val::Vloc(u) => json!({"type": "loc", "value": u}),
+        Val::Vundef => json!({"type": "undef"}),
+        Val::Vsize(_) | Val::Vaddr(_) | Val::Vthread(_) | Val::Vchan(_) | Val::Vmutex(_) => panic!("{:?} only exists at runtime", val),
+        Val::Vlabel(label) => json!({"type": "label", "value": label}),
+    }
+}
+
+fn instr_to_json(instr: &Instr) -> Value {
+    let op = instr.mnemonic().unwrap_or_else(|| panic!("{} is not an instruction", instr));
+    match instr {
+        Instr::Push(val) => json!({"op": op, "value": val_to_json(val)}),
+        Instr::Unary(unop) => json!({"op": op, "arg": unop.mnemonic()}),
+        Instr::Binary(binop) => json!({"op": op, "arg": binop.mnemonic()}),
+        Instr::Peek(u) | Instr::Var(u) | Instr::Store(u) | Instr::SetFrame(u) => json!({"op": op, "arg": u}),
+        _ => json!({"op": op}),
+    }
+}
+
+fn val_from_json(value: &Value) -> Result<Val, String> {
+    let kind = value.get("type").and_then(Value::as_str).ok_or("value has no type")?;
+    let inner = value.get("value");
+    let missing = || format!("{} value is missing or of the wrong type", kind);
+    match kind {
+        "unit" => Ok(Val::Vunit),
+        "undef" => Ok(Val::Vundef),
+        "i32" => inner.and_then(Value::as_i64).map(|i| Val::Vi32(i as i32)).ok_or_else(missing),
+        "bool" => inner.and_then(Value::as_bool).map(Val::Vbool).ok_or_else(missing),
+        "loc" => inner.and_then(Value::as_u64).map(|u| Val::Vloc(u as u32)).ok_or_else(missing),
+        "label" => inner.and_then(Value::as_str).map(|l| Val::Vlabel(l.to_string())).ok_or_else(missing),
+        other => Err(format!("unknown value type {}", other)),
+    }
+}
+
+fn instr_from_json(value: &Value) -> Result<Instr, String> {
+    let op = value.get("op").and_then(Value::as_str).ok_or("op is missing")?;
+    let arg = value.get("arg");
+    let u32_arg = || arg.and_then(Value::as_u64).map(|u| u as u32).ok_or_else(|| format!("{} needs a u32 arg", op));
+    let str_arg = || arg.and_then(Value::as_str).ok_or_else(|| format!("{} needs an operator arg", op));
+    match op {
+        "push" => Ok(Instr::Push(val_from_json(value.get("value").ok_or("push needs a value")?)?)),
+        "pop" => Ok(Instr::Pop),
+        "peek" => Ok(Instr::Peek(u32_arg()?)),
+        "unary" => Unop::from_mnemonic(str_arg()?).map(Instr::Unary).ok_or_else(|| "unknown unary operator".to_string()),
+        "binary" => Binop::from_mnemonic(str_arg()?).map(Instr::Binary).ok_or_else(|| "unknown binary operator".to_string()),
+        "swap" => Ok(Instr::Swap),
+        "alloc" => Ok(Instr::Alloc),
+        "set" => Ok(Instr::Set),
+        "get" => Ok(Instr::Get),
+        "var" => Ok(Instr::Var(u32_arg()?)),
+        "store" => Ok(Instr::Store(u32_arg()?)),
+        "setframe" => Ok(Instr::SetFrame(u32_arg()?)),
+        "call" => Ok(Instr::Call),
+        "ret" => Ok(Instr::Ret),
+        "branch" => Ok(Instr::Branch),
+        "halt" => Ok(Instr::Halt),
+        "spawn" => Ok(Instr::Spawn),
+        "join" => Ok(Instr::Join),
+        "chan" => Ok(Instr::Chan),
+        "send" => Ok(Instr::Send),
+        "recv" => Ok(Instr::Recv),
+        "close" => Ok(Instr::Close),
+        "mutex" => Ok(Instr::Mutex),
+        "acquire" => Ok(Instr::Acquire),
+        "release" => Ok(Instr::Release),
+        "cas" => Ok(Instr::Cas),
+        "print" => Ok(Instr::Print),
+        other => Err(format!("unknown op {}", other)),
+    }
+}
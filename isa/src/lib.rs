@@ -0,0 +1,437 @@
+//! The Grumpy instruction set: the instructions and values every tool agrees
+//! on, their binary encoding and their assembly mnemonics.
+
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+use std::fmt;
+use std::slice::Iter;
+
+pub mod json;
+pub use json::{load_program, Program};
+
+pub type Address = usize;
+
+/// Every instruction mnemonic with the opcode it encodes to.
+pub const OPCODES: [(&str, u8); 27] = [
+    ("push", 0),
+    ("pop", 1),
+    ("peek", 2),
+    ("unary", 3),
+    ("binary", 4),
+    ("swap", 5),
+    ("alloc", 6),
+    ("set", 7),
+    ("get", 8),
+    ("var", 9),
+    ("store", 10),
+    ("setframe", 11),
+    ("call", 12),
+    ("ret", 13),
+    ("branch", 14),
+    ("halt", 15),
+    ("spawn", 16),
+    ("join", 17),
+    ("chan", 18),
+    ("send", 19),
+    ("print", 20),
+    ("recv", 21),
+    ("close", 22),
+    ("mutex", 23),
+    ("acquire", 24),
+    ("release", 25),
+    ("cas", 26),
+];
+
+/// A value. `Vsize`, `Vaddr`, `Vthread`, `Vchan` and `Vmutex` only exist
+/// inside a running VM and `Vlabel` only until the assembler resolves it, so
+/// none of them can be encoded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Val {
+    Vunit,
+    Vi32(i32),
+    Vbool(bool),
+    Vloc(u32),
+    Vundef,
+    Vsize(i32),
+    Vaddr(Address),
+    Vthread(u32),
+    Vchan(u32),
+    Vmutex(u32),
+    Vlabel(String),
+}
+
+/// An instruction. `Label` marks a position in assembly and takes no space
+/// in the encoded program.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instr {
+    Label(String),
+    Push(Val),
+    Pop,
+    Peek(u32),
+    Unary(Unop),
+    Binary(Binop),
+    Swap,
+    Alloc,
+    Set,
+    Get,
+    Var(u32),
+    Store(u32),
+    SetFrame(u32),
+    Call,
+    Ret,
+    Branch,
+    Halt,
+    Spawn,
+    Join,
+    Chan,
+    Send,
+    Recv,
+    Close,
+    Mutex,
+    Acquire,
+    Release,
+    Cas,
+    Print,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Unop {
+    Neg,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Binop {
+    Add,
+    Mul,
+    Sub,
+    Div,
+    Lt,
+    Eq,
+}
+
+/// The opcode of `mnemonic`, None if no instruction is called that.
+pub fn opcode(mnemonic: &str) -> Option<u8> {
+    OPCODES.iter().find(|(name, _)| *name == mnemonic).map(|(_, op)| *op)
+}
+
+impl Instr {
+    /// The assembly name of the instruction, None for a label.
+    pub fn mnemonic(&self) -> Option<&'static str> {
+        let name = match self {
+            Instr::Label(_) => return None,
+            Instr::Push(_) => "push",
+            Instr::Pop => "pop",
+            Instr::Peek(_) => "peek",
+            Instr::Unary(_) => "unary",
+            Instr::Binary(_) => "binary",
+            Instr::Swap => "swap",
+            Instr::Alloc => "alloc",
+            Instr::Set => "set",
+            Instr::Get => "get",
+            Instr::Var(_) => "var",
+            Instr::Store(_) => "store",
+            Instr::SetFrame(_) => "setframe",
+            Instr::Call => "call",
+            Instr::Ret => "ret",
+            Instr::Branch => "branch",
+            Instr::Halt => "halt",
+            Instr::Spawn => "spawn",
+            Instr::Join => "join",
+            Instr::Chan => "chan",
+            Instr::Send => "send",
+            Instr::Recv => "recv",
+            Instr::Close => "close",
+            Instr::Mutex => "mutex",
+            Instr::Acquire => "acquire",
+            Instr::Release => "release",
+            Instr::Cas => "cas",
+            Instr::Print => "print",
+        };
+        Some(name)
+    }
+
+    /// The byte the instruction is encoded with, None for a label.
+    pub fn opcode(&self) -> Option<u8> {
+        self.mnemonic().and_then(opcode)
+    }
+}
+
+impl Unop {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Unop::Neg => "neg",
+        }
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Unop> {
+        match mnemonic {
+            "neg" => Some(Unop::Neg),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> u8 {
+        match self {
+            Unop::Neg => 0,
+        }
+    }
+}
+
+impl Binop {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Binop::Add => "+",
+            Binop::Mul => "*",
+            Binop::Sub => "-",
+            Binop::Div => "/",
+            Binop::Lt => "<",
+            Binop::Eq => "==",
+        }
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Binop> {
+        match mnemonic {
+            "+" => Some(Binop::Add),
+            "*" => Some(Binop::Mul),
+            "-" => Some(Binop::Sub),
+            "/" => Some(Binop::Div),
+            "<" => Some(Binop::Lt),
+            "==" => Some(Binop::Eq),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> u8 {
+        match self {
+            Binop::Add => 0,
+            Binop::Mul => 1,
+            Binop::Sub => 2,
+            Binop::Div => 3,
+            Binop::Lt => 4,
+            Binop::Eq => 5,
+        }
+    }
+}
+
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Val::Vunit => write!(f, "tt"),
+            Val::Vi32(i) => write!(f, "{}", i),
+            Val::Vbool(b) => write!(f, "{}", b),
+            Val::Vloc(u) => write!(f, "loc:{}", u),
+            Val::Vundef => write!(f, "undef"),
+            Val::Vsize(i) => write!(f, "size:{}", i),
+            Val::Vaddr(a) => write!(f, "addr:{}", a),
+            Val::Vthread(t) => write!(f, "thread:{}", t),
+            Val::Vchan(c) => write!(f, "chan:{}", c),
+            Val::Vmutex(m) => write!(f, "mutex:{}", m),
+            Val::Vlabel(label) => write!(f, "{}", label),
+        }
+    }
+}
+
+impl fmt::Display for Unop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())
+    }
+}
+
+impl fmt::Display for Binop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())
+    }
+}
+
+/// Formats the instruction as one line of assembly.
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = self.mnemonic().unwrap_or("");
+        match self {
+            Instr::Label(label) => write!(f, "{}:", label),
+            Instr::Push(v) => write!(f, "{} {}", name, v),
+            Instr::Unary(unop) => write!(f, "{} {}", name, unop),
+            Instr::Binary(binop) => write!(f, "{} {}", name, binop),
+            Instr::Peek(u) | Instr::Var(u) | Instr::Store(u) | Instr::SetFrame(u) => write!(f, "{} {}", name, u),
+            _ => write!(f, "{}", name),
+        }
+    }
+}
+
+pub trait ToBin {
+    fn to_bin(&self) -> Vec<u8>;
+}
+
+impl ToBin for u32 {
+    fn to_bin(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.write_u32::<BigEndian>(*self).unwrap();
+        bytes
+    }
+}
+
+impl ToBin for i32 {
+    fn to_bin(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.write_i32::<BigEndian>(*self).unwrap();
+        bytes
+    }
+}
+
+impl ToBin for Val {
+    fn to_bin(&self) -> Vec<u8> {
+        match self {
+            Val::Vunit => vec![0],
+            Val::Vi32(i) => [vec![1], i.to_bin()].concat(),
+            Val::Vbool(true) => vec![2],
+            Val::Vbool(false) => vec![3],
+            Val::Vloc(u) => [vec![4], u.to_bin()].concat(),
+            Val::Vundef => vec![5],
+            Val::Vsize(_) | Val::Vaddr(_) | Val::Vthread(_) | Val::Vchan(_) | Val::Vmutex(_) => panic!("{:?} only exists at runtime", self),
+            Val::Vlabel(label) => panic!("Label {} was never resolved", label),
+        }
+    }
+}
+
+impl ToBin for Unop {
+    fn to_bin(&self) -> Vec<u8> {
+        vec![self.code()]
+    }
+}
+
+impl ToBin for Binop {
+    fn to_bin(&self) -> Vec<u8> {
+        vec![self.code()]
+    }
+}
+
+impl ToBin for Instr {
+    fn to_bin(&self) -> Vec<u8> {
+        let op = match self.opcode() {
+            Some(op) => op,
+            None => panic!("{} has no encoding", self),
+        };
+        let operand = match self {
+            Instr::Push(v) => v.to_bin(),
+            Instr::Unary(unop) => unop.to_bin(),
+            Instr::Binary(binop) => binop.to_bin(),
+            Instr::Peek(u) | Instr::Var(u) | Instr::Store(u) | Instr::SetFrame(u) => u.to_bin(),
+            _ => Vec::new(),
+        };
+        [vec![op], operand].concat()
+    }
+}
+
+/// Decoding fails with a message instead of panicking, so a VM can report a
+/// corrupt or truncated program.
+pub trait FromBin: Sized {
+    fn from_bin(iter: &mut Iter<u8>) -> Result<Self, String>;
+}
+
+fn byte(iter: &mut Iter<u8>) -> Result<u8, String> {
+    iter.next().copied().ok_or_else(|| "Unexpected end of program".to_string())
+}
+
+fn word(iter: &mut Iter<u8>) -> Result<[u8; 4], String> {
+    Ok([byte(iter)?, byte(iter)?, byte(iter)?, byte(iter)?])
+}
+
+impl FromBin for u32 {
+    fn from_bin(iter: &mut Iter<u8>) -> Result<Self, String> {
+        Ok(BigEndian::read_u32(&word(iter)?))
+    }
+}
+
+impl FromBin for i32 {
+    fn from_bin(iter: &mut Iter<u8>) -> Result<Self, String> {
+        Ok(BigEndian::read_i32(&word(iter)?))
+    }
+}
+
+impl FromBin for Val {
+    fn from_bin(iter: &mut Iter<u8>) -> Result<Self, String> {
+        match byte(iter)? {
+            0 => Ok(Val::Vunit),
+            1 => Ok(Val::Vi32(i32::from_bin(iter)?)),
+            2 => Ok(Val::Vbool(true)),
+            3 => Ok(Val::Vbool(false)),
+            4 => Ok(Val::Vloc(u32::from_bin(iter)?)),
+            5 => Ok(Val::Vundef),
+            tag => Err(format!("Invalid Value tag {}", tag)),
+        }
+    }
+}
+
+impl FromBin for Unop {
+    fn from_bin(iter: &mut Iter<u8>) -> Result<Self, String> {
+        match byte(iter)? {
+            0 => Ok(Unop::Neg),
+            code => Err(format!("Invalid Unary operator {}", code)),
+        }
+    }
+}
+
+impl FromBin for Binop {
+    fn from_bin(iter: &mut Iter<u8>) -> Result<Self, String> {
+        match byte(iter)? {
+            0 => Ok(Binop::Add),
+            1 => Ok(Binop::Mul),
+            2 => Ok(Binop::Sub),
+            3 => Ok(Binop::Div),
+            4 => Ok(Binop::Lt),
+            5 => Ok(Binop::Eq),
+            code => Err(format!("Invalid Binary operator {}", code)),
+        }
+    }
+}
+
+impl FromBin for Instr {
+    fn from_bin(iter: &mut Iter<u8>) -> Result<Self, String> {
+        match byte(iter)? {
+            0 => Ok(Instr::Push(Val::from_bin(iter)?)),
+            1 => Ok(Instr::Pop),
+            2 => Ok(Instr::Peek(u32::from_bin(iter)?)),
+            3 => Ok(Instr::Unary(Unop::from_bin(iter)?)),
+            4 => Ok(Instr::Binary(Binop::from_bin(iter)?)),
+            5 => Ok(Instr::Swap),
+            6 => Ok(Instr::Alloc),
+            7 => Ok(Instr::Set),
+            8 => Ok(Instr::Get),
+            9 => Ok(Instr::Var(u32::from_bin(iter)?)),
+            10 => Ok(Instr::Store(u32::from_bin(iter)?)),
+            11 => Ok(Instr::SetFrame(u32::from_bin(iter)?)),
+            12 => Ok(Instr::Call),
+            13 => Ok(Instr::Ret),
+            14 => Ok(Instr::Branch),
+            15 => Ok(Instr::Halt),
+            16 => Ok(Instr::Spawn),
+            17 => Ok(Instr::Join),
+            18 => Ok(Instr::Chan),
+            19 => Ok(Instr::Send),
+            20 => Ok(Instr::Print),
+            21 => Ok(Instr::Recv),
+            22 => Ok(Instr::Close),
+            23 => Ok(Instr::Mutex),
+            24 => Ok(Instr::Acquire),
+            25 => Ok(Instr::Release),
+            26 => Ok(Instr::Cas),
+            op => Err(format!("Invalid Instruction opcode {}", op)),
+        }
+    }
+}
+
+/// Encodes a whole program as an executable `.o`: the instruction count
+/// followed by the instructions.
+pub fn encode_program(program: &[Instr]) -> Vec<u8> {
+    let mut bytes = (program.len() as u32).to_bin();
+    for instr in program {
+        bytes.append(&mut instr.to_bin());
+    }
+    bytes
+}
+
+/// Decodes an executable `.o` back into its instructions.
+pub fn decode_program(bytes: &[u8]) -> Result<Vec<Instr>, String> {
+    let mut iter = bytes.iter();
+    let count = u32::from_bin(&mut iter)?;
+    (0..count).map(|_| Instr::from_bin(&mut iter)).collect()
+}
@@ -0,0 +1,47 @@
+use grumpy_isa::*;
+
+fn fact_like() -> Vec<Instr> {
+    vec![Instr::SetFrame(0), Instr::Push(Val::Vlabel("Lmain".to_string())), Instr::Call, Instr::Halt,
+         Instr::Label("Lmain".to_string()), Instr::Label("_L1".to_string()),
+         Instr::Push(Val::Vi32(-3)), Instr::Push(Val::Vbool(true)), Instr::Push(Val::Vunit),
+         Instr::Push(Val::Vundef), Instr::Push(Val::Vloc(2)), Instr::Binary(Binop::Lt),
+         Instr::Unary(Unop::Neg), Instr::Var(1), Instr::Ret]
+}
+
+#[test]
+fn labels_come_out_of_the_instruction_stream() {
+    let program = Program::from_instrs(&fact_like());
+    assert_eq!(program.instrs.len(), 13);
+    assert_eq!(program.labels["Lmain"], 4);
+    assert_eq!(program.labels["_L1"], 4);
+    assert_eq!(program.with_labels(), fact_like());
+    assert_eq!(program.resolved().unwrap()[1], Instr::Push(Val::Vloc(4)));
+}
+
+#[test]
+fn programs_round_trip_through_json() {
+    let mut program = Program::from_instrs(&fact_like());
+    program.metadata.insert("producer".to_string(), "test".to_string());
+    let text = program.to_json();
+    assert!(text.contains(r#"{"op":"push","value":{"type":"label","value":"Lmain"}}"#), "{}", text);
+    assert!(text.contains(r#"{"op":"binary","arg":"<"}"#), "{}", text);
+    assert_eq!(Program::from_json(&text), Ok(program));
+}
+
+#[test]
+fn load_program_reads_either_format() {
+    let program = Program::from_instrs(&fact_like());
+    let resolved = program.resolved().unwrap();
+    assert_eq!(load_program(program.to_json().as_bytes()), Ok(resolved.clone()));
+    assert_eq!(load_program(&encode_program(&resolved)), Ok(resolved));
+}
+
+#[test]
+fn bad_json_is_an_error() {
+    assert!(Program::from_json("{}").is_err());
+    assert!(Program::from_json(r#"{"grumpy": 2, "instructions": []}"#).is_err());
+    assert!(Program::from_json(r#"{"grumpy": 1, "instructions": [{"op": "jump"}]}"#).is_err());
+    assert!(Program::from_json(r#"{"grumpy": 1, "instructions": [{"op": "var"}]}"#).is_err());
+    let unresolved = Program::from_json(r#"{"grumpy": 1, "instructions": [{"op": "push", "value": {"type": "label", "value": "Lx"}}]}"#);
+    assert!(unresolved.unwrap().resolved().is_err());
+}
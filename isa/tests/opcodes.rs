@@ -0,0 +1,96 @@
+use grumpy_isa::*;
+
+//Changing any of these breaks every .o file already written
+#[test]
+fn opcode_table_is_fixed() {
+    let expected = [("push", 0), ("pop", 1), ("peek", 2), ("unary", 3), ("binary", 4), ("swap", 5),
+                    ("alloc", 6), ("set", 7), ("get", 8), ("var", 9), ("store", 10), ("setframe", 11),
+                    ("call", 12), ("ret", 13), ("branch", 14), ("halt", 15), ("spawn", 16), ("join", 17),
+                    ("chan", 18), ("send", 19), ("print", 20), ("recv", 21), ("close", 22),
+                    ("mutex", 23), ("acquire", 24), ("release", 25), ("cas", 26)];
+    assert_eq!(OPCODES, expected);
+}
+
+#[test]
+fn instructions_encode_to_their_opcodes() {
+    let cases = vec![
+        (Instr::Push(Val::Vi32(-2)), vec![0, 1, 0xff, 0xff, 0xff, 0xfe]),
+        (Instr::Pop, vec![1]),
+        (Instr::Peek(3), vec![2, 0, 0, 0, 3]),
+        (Instr::Unary(Unop::Neg), vec![3, 0]),
+        (Instr::Binary(Binop::Eq), vec![4, 5]),
+        (Instr::Swap, vec![5]),
+        (Instr::Alloc, vec![6]),
+        (Instr::Set, vec![7]),
+        (Instr::Get, vec![8]),
+        (Instr::Var(1), vec![9, 0, 0, 0, 1]),
+        (Instr::Store(258), vec![10, 0, 0, 1, 2]),
+        (Instr::SetFrame(4), vec![11, 0, 0, 0, 4]),
+        (Instr::Call, vec![12]),
+        (Instr::Ret, vec![13]),
+        (Instr::Branch, vec![14]),
+        (Instr::Halt, vec![15]),
+        (Instr::Spawn, vec![16]),
+        (Instr::Join, vec![17]),
+        (Instr::Chan, vec![18]),
+        (Instr::Send, vec![19]),
+        (Instr::Recv, vec![21]),
+        (Instr::Close, vec![22]),
+        (Instr::Mutex, vec![23]),
+        (Instr::Acquire, vec![24]),
+        (Instr::Release, vec![25]),
+        (Instr::Cas, vec![26]),
+        (Instr::Print, vec![20]),
+    ];
+    for (instr, bytes) in cases {
+        assert_eq!(instr.to_bin(), bytes, "{}", instr);
+        assert_eq!(Some(bytes[0]), opcode(instr.mnemonic().unwrap()));
+        assert_eq!(Instr::from_bin(&mut bytes.iter()), Ok(instr));
+    }
+}
+
+#[test]
+fn values_and_operators_encode_to_their_tags() {
+    assert_eq!(Val::Vunit.to_bin(), vec![0]);
+    assert_eq!(Val::Vi32(7).to_bin(), vec![1, 0, 0, 0, 7]);
+    assert_eq!(Val::Vbool(true).to_bin(), vec![2]);
+    assert_eq!(Val::Vbool(false).to_bin(), vec![3]);
+    assert_eq!(Val::Vloc(9).to_bin(), vec![4, 0, 0, 0, 9]);
+    assert_eq!(Val::Vundef.to_bin(), vec![5]);
+
+    let binops = [Binop::Add, Binop::Mul, Binop::Sub, Binop::Div, Binop::Lt, Binop::Eq];
+    for (code, binop) in binops.iter().enumerate() {
+        assert_eq!(binop.to_bin(), vec![code as u8]);
+        assert_eq!(Binop::from_mnemonic(binop.mnemonic()).as_ref(), Some(binop));
+    }
+    assert_eq!(Unop::Neg.to_bin(), vec![0]);
+}
+
+#[test]
+fn mnemonics_format_as_assembly() {
+    assert_eq!(Instr::Push(Val::Vunit).to_string(), "push tt");
+    assert_eq!(Instr::Push(Val::Vloc(3)).to_string(), "push loc:3");
+    assert_eq!(Instr::Push(Val::Vlabel("Lmain".to_string())).to_string(), "push Lmain");
+    assert_eq!(Instr::Binary(Binop::Eq).to_string(), "binary ==");
+    assert_eq!(Instr::Unary(Unop::Neg).to_string(), "unary neg");
+    assert_eq!(Instr::SetFrame(2).to_string(), "setframe 2");
+    assert_eq!(Instr::Label("_L1".to_string()).to_string(), "_L1:");
+    assert_eq!(Instr::Label("_L1".to_string()).opcode(), None);
+}
+
+#[test]
+fn programs_round_trip() {
+    let program = vec![Instr::SetFrame(0), Instr::Push(Val::Vloc(4)), Instr::Call, Instr::Halt,
+                       Instr::Push(Val::Vbool(false)), Instr::Ret];
+    let bytes = encode_program(&program);
+    assert_eq!(&bytes[..4], &[0, 0, 0, 6]);
+    assert_eq!(decode_program(&bytes), Ok(program));
+}
+
+#[test]
+fn bad_programs_are_errors() {
+    assert!(decode_program(&[0, 0, 0, 1, 99]).is_err());
+    assert!(decode_program(&[0, 0, 0, 1, 0, 6]).is_err());
+    assert!(decode_program(&[0, 0, 0, 2, 1]).is_err());
+    assert!(decode_program(&[0, 0]).is_err());
+}